@@ -0,0 +1,9 @@
+-- Lista de access tokens revocados (logout), identificados por su claim `jti`.
+-- Las entradas se purgan automáticamente una vez que el token habría expirado.
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti TEXT PRIMARY KEY,
+    expires_at TEXT NOT NULL,
+    revoked_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_revoked_tokens_expires_at ON revoked_tokens(expires_at);
@@ -0,0 +1,13 @@
+-- Claves asimétricas para firmar access tokens (RS256 / EdDSA).
+-- La clave con `retired_at` NULL es la de firma; las retiradas solo verifican
+-- hasta que expira el último token que pudieron firmar.
+CREATE TABLE IF NOT EXISTS jwt_signing_keys (
+    kid TEXT PRIMARY KEY,
+    algorithm TEXT NOT NULL,
+    private_key_pem TEXT NOT NULL,
+    public_jwk TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    retired_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_jwt_signing_keys_algorithm ON jwt_signing_keys(algorithm);
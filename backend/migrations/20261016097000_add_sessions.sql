@@ -0,0 +1,20 @@
+-- Sesiones por dispositivo. El `id` de la sesión es el `family_id` de sus refresh
+-- tokens y viaja en el claim `sid` de los access tokens.
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    ip_address TEXT,
+    user_agent TEXT,
+    created_at TEXT NOT NULL,
+    last_active_at TEXT NOT NULL,
+    revoked_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions(user_id);
+
+-- Las familias de refresh tokens vigentes pasan a ser sesiones (sin datos del dispositivo).
+INSERT INTO sessions (id, user_id, created_at, last_active_at)
+SELECT family_id, user_id, MIN(created_at), MAX(created_at)
+FROM refresh_tokens
+WHERE revoked_at IS NULL
+GROUP BY family_id, user_id;
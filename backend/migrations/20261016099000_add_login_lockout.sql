@@ -0,0 +1,8 @@
+-- Bloqueo progresivo por cuenta a partir de `login_attempts`.
+-- `cleared_at` marca los fallos que ya no cuentan (tras un login correcto o un desbloqueo).
+ALTER TABLE login_attempts ADD COLUMN cleared_at TEXT;
+
+-- Los intentos se agrupan por email en minúsculas (ver `normalize_attempt_email`).
+UPDATE login_attempts SET email = lower(trim(email)) WHERE email IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_login_attempts_email ON login_attempts(email, attempted_at);
@@ -0,0 +1,23 @@
+-- Registro solo por invitación.
+
+-- Ajustes modificables en tiempo de ejecución (tienen prioridad sobre la configuración).
+CREATE TABLE IF NOT EXISTS app_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Invitaciones emitidas por administradores. El código se guarda como hash.
+CREATE TABLE IF NOT EXISTS invites (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    code_hash TEXT UNIQUE NOT NULL,
+    code_prefix TEXT NOT NULL,
+    email TEXT,
+    role TEXT CHECK(role IN ('user', 'admin')) NOT NULL DEFAULT 'user',
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,
+    used_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    revoked_at TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,92 @@
+-- Roles personalizados y permisos granulares.
+
+-- Catálogo de roles. Los roles de sistema (`user`, `admin`) no pueden modificarse ni eliminarse.
+CREATE TABLE IF NOT EXISTS roles (
+    name TEXT PRIMARY KEY,
+    description TEXT,
+    is_system BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Permisos concedidos por cada rol (p. ej. 'tasks:read_all').
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE ON UPDATE CASCADE,
+    permission TEXT NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+
+INSERT OR IGNORE INTO roles (name, description, is_system) VALUES
+    ('user', 'Usuario estándar: solo gestiona sus propias tareas', TRUE),
+    ('admin', 'Administrador con todos los permisos', TRUE),
+    ('auditor', 'Auditor de solo lectura', FALSE),
+    ('support', 'Soporte: consulta y edita tareas ajenas, pero no las elimina', FALSE);
+
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES
+    ('admin', 'tasks:read_all'),
+    ('admin', 'tasks:update_all'),
+    ('admin', 'tasks:delete_all'),
+    ('admin', 'users:read'),
+    ('admin', 'users:manage'),
+    ('admin', 'invites:manage'),
+    ('admin', 'roles:manage'),
+    ('admin', 'stats:read'),
+    ('auditor', 'tasks:read_all'),
+    ('auditor', 'users:read'),
+    ('auditor', 'stats:read'),
+    ('support', 'tasks:read_all'),
+    ('support', 'tasks:update_all'),
+    ('support', 'users:read');
+
+-- SQLite no permite modificar un CHECK con ALTER TABLE: se reconstruyen `users` e
+-- `invites` para que el rol pase a ser una referencia a `roles`. `init_db` ejecuta las
+-- migraciones con las claves foráneas desactivadas, así que el DROP no borra en cascada
+-- las tareas, sesiones o tokens de cada usuario.
+CREATE TABLE users_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    email TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+    role TEXT NOT NULL DEFAULT 'user' REFERENCES roles(name) ON UPDATE CASCADE,
+    email_verified_at TEXT,
+    totp_secret TEXT,
+    totp_enabled_at TEXT,
+    totp_last_step INTEGER
+);
+
+INSERT INTO users_new (id, name, email, password_hash, created_at, role, email_verified_at,
+                       totp_secret, totp_enabled_at, totp_last_step)
+SELECT id, name, email, password_hash, created_at, role, email_verified_at,
+       totp_secret, totp_enabled_at, totp_last_step
+FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE INDEX IF NOT EXISTS idx_users_email ON users(email);
+CREATE INDEX IF NOT EXISTS idx_users_role ON users(role);
+
+CREATE TABLE invites_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    code_hash TEXT UNIQUE NOT NULL,
+    code_prefix TEXT NOT NULL,
+    email TEXT,
+    role TEXT NOT NULL DEFAULT 'user' REFERENCES roles(name) ON UPDATE CASCADE,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TEXT NOT NULL,
+    used_at TEXT,
+    used_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    revoked_at TEXT,
+    created_at TEXT DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO invites_new (id, code_hash, code_prefix, email, role, created_by, expires_at,
+                         used_at, used_by, revoked_at, created_at)
+SELECT id, code_hash, code_prefix, email, role, created_by, expires_at,
+       used_at, used_by, revoked_at, created_at
+FROM invites;
+
+DROP TABLE invites;
+ALTER TABLE invites_new RENAME TO invites;
+
+CREATE INDEX IF NOT EXISTS idx_invites_role ON invites(role);
@@ -0,0 +1,22 @@
+-- Registro de auditoría de la suplantación de usuarios.
+
+-- Cada suplantación iniciada y cada petición hecha con un token de suplantación.
+-- Las filas se conservan aunque se eliminen las cuentas implicadas.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    actor_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    action TEXT NOT NULL,
+    method TEXT,
+    path TEXT,
+    ip_address TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor_id ON audit_log(actor_id);
+CREATE INDEX IF NOT EXISTS idx_audit_log_user_id ON audit_log(user_id);
+
+INSERT OR IGNORE INTO role_permissions (role, permission) VALUES
+    ('admin', 'users:impersonate'),
+    ('admin', 'audit:read'),
+    ('auditor', 'audit:read');
@@ -0,0 +1,6 @@
+-- Estado de las cuentas: permite suspender o desactivar usuarios sin borrar sus datos.
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active' CHECK(status IN ('active', 'suspended', 'deactivated'));
+ALTER TABLE users ADD COLUMN status_reason TEXT;
+ALTER TABLE users ADD COLUMN status_changed_at TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_users_status ON users(status);
@@ -0,0 +1,3 @@
+-- Versión de los tokens de cada usuario. El rol viaja en el JWT; subir la versión invalida
+-- los tokens emitidos antes de un cambio de rol, de estado o de la eliminación de la cuenta.
+ALTER TABLE users ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0;
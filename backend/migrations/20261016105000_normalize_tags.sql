@@ -0,0 +1,56 @@
+-- Tags normalizados: cada usuario tiene su propio catálogo de tags y las tareas los
+-- referencian en `task_tags`, en lugar de guardarlos como texto separado por comas.
+
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, name)
+);
+
+-- `position` conserva el orden en que se indicaron los tags de la tarea.
+CREATE TABLE IF NOT EXISTS task_tags (
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (task_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_tags_tag_id ON task_tags(tag_id);
+
+-- Migrar los tags existentes: se separan por comas, sin espacios y en minúsculas.
+WITH RECURSIVE split(task_id, user_id, position, name, rest) AS (
+    SELECT id, user_id, -1, '', COALESCE(tags, '') || ',' FROM tasks
+    UNION ALL
+    SELECT task_id, user_id, position + 1,
+           LOWER(TRIM(SUBSTR(rest, 1, INSTR(rest, ',') - 1))),
+           SUBSTR(rest, INSTR(rest, ',') + 1)
+    FROM split WHERE rest != ''
+)
+INSERT OR IGNORE INTO tags (user_id, name)
+SELECT DISTINCT user_id, name FROM split WHERE name != '';
+
+WITH RECURSIVE split(task_id, user_id, position, name, rest) AS (
+    SELECT id, user_id, -1, '', COALESCE(tags, '') || ',' FROM tasks
+    UNION ALL
+    SELECT task_id, user_id, position + 1,
+           LOWER(TRIM(SUBSTR(rest, 1, INSTR(rest, ',') - 1))),
+           SUBSTR(rest, INSTR(rest, ',') + 1)
+    FROM split WHERE rest != ''
+)
+INSERT OR IGNORE INTO task_tags (task_id, tag_id, position)
+SELECT s.task_id, tg.id, MIN(s.position)
+FROM split s
+JOIN tags tg ON tg.user_id = s.user_id AND tg.name = s.name
+WHERE s.name != ''
+GROUP BY s.task_id, tg.id;
+
+ALTER TABLE tasks DROP COLUMN tags;
+
+-- Representación separada por comas que siguen devolviendo las respuestas de tareas.
+CREATE VIEW IF NOT EXISTS task_tag_lists AS
+SELECT tt.task_id, GROUP_CONCAT(tg.name, ',' ORDER BY tt.position) AS tags
+FROM task_tags tt
+JOIN tags tg ON tg.id = tt.tag_id
+GROUP BY tt.task_id;
@@ -0,0 +1,18 @@
+-- `tasks.assigned_to` deja de ser texto libre y pasa a referenciar `users(id)`.
+
+ALTER TABLE tasks ADD COLUMN assignee_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
+
+-- Migrar las asignaciones existentes: el texto se compara, sin distinguir mayúsculas, con el
+-- nombre y el email de los usuarios. Si no corresponde a un único usuario, la tarea queda sin asignar.
+UPDATE tasks SET assignee_id = (
+    SELECT CASE WHEN COUNT(*) = 1 THEN MIN(u.id) END
+    FROM users u
+    WHERE LOWER(u.name) = LOWER(TRIM(tasks.assigned_to))
+       OR LOWER(u.email) = LOWER(TRIM(tasks.assigned_to))
+)
+WHERE assigned_to IS NOT NULL AND TRIM(assigned_to) != '';
+
+ALTER TABLE tasks DROP COLUMN assigned_to;
+ALTER TABLE tasks RENAME COLUMN assignee_id TO assigned_to;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_assigned_to ON tasks(assigned_to);
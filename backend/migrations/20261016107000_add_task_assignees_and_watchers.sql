@@ -0,0 +1,41 @@
+-- Varias personas asignadas por tarea y usuarios que siguen sus cambios.
+
+CREATE TABLE IF NOT EXISTS task_assignees (
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    assigned_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (task_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_assignees_user_id ON task_assignees(user_id);
+
+CREATE TABLE IF NOT EXISTS task_watchers (
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (task_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_watchers_user_id ON task_watchers(user_id);
+
+-- La asignación única pasa a ser la primera persona asignada de la tarea.
+INSERT OR IGNORE INTO task_assignees (task_id, user_id)
+SELECT id, assigned_to FROM tasks WHERE assigned_to IS NOT NULL;
+
+DROP INDEX IF EXISTS idx_tasks_assigned_to;
+ALTER TABLE tasks DROP COLUMN assigned_to;
+
+-- Listas JSON (`[{"id": 2, "name": "Admin User"}]`) que devuelven las respuestas de tareas,
+-- en el orden en que se añadieron. Se concatenan como texto porque `json_group_array` con
+-- ORDER BY trataría cada objeto como un string.
+CREATE VIEW IF NOT EXISTS task_assignee_lists AS
+SELECT ta.task_id, '[' || GROUP_CONCAT(json_object('id', u.id, 'name', u.name), ',' ORDER BY ta.rowid) || ']' AS assignees
+FROM task_assignees ta
+JOIN users u ON u.id = ta.user_id
+GROUP BY ta.task_id;
+
+CREATE VIEW IF NOT EXISTS task_watcher_lists AS
+SELECT tw.task_id, '[' || GROUP_CONCAT(json_object('id', u.id, 'name', u.name), ',' ORDER BY tw.rowid) || ']' AS watchers
+FROM task_watchers tw
+JOIN users u ON u.id = tw.user_id
+GROUP BY tw.task_id;
@@ -0,0 +1,24 @@
+-- Subtareas (una tarea puede colgar de otra) y listas de comprobación dentro de una tarea.
+
+-- Al eliminar una tarea se eliminan también sus subtareas.
+ALTER TABLE tasks ADD COLUMN parent_id INTEGER REFERENCES tasks(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_parent_id ON tasks(parent_id);
+
+CREATE TABLE IF NOT EXISTS checklist_items (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    title TEXT NOT NULL,
+    done BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_checklist_items_task_id ON checklist_items(task_id);
+
+-- Lista JSON (`[{"id": 1, "title": "...", "done": false}]`) que devuelven las respuestas de tareas.
+CREATE VIEW IF NOT EXISTS task_checklist_lists AS
+SELECT c.task_id,
+       '[' || GROUP_CONCAT(json_object('id', c.id, 'title', c.title, 'done', json(CASE WHEN c.done THEN 'true' ELSE 'false' END)), ',' ORDER BY c.id) || ']' AS items
+FROM checklist_items c
+GROUP BY c.task_id;
@@ -0,0 +1,17 @@
+-- Dependencias entre tareas: `task_id` está bloqueada por `blocker_id` hasta que esta se termine.
+
+CREATE TABLE IF NOT EXISTS task_dependencies (
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    blocker_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (task_id, blocker_id),
+    CHECK (task_id != blocker_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_task_dependencies_blocker_id ON task_dependencies(blocker_id);
+
+-- Lista JSON con los IDs de las tareas que bloquean a cada tarea, en el orden en que se añadieron.
+CREATE VIEW IF NOT EXISTS task_blocker_lists AS
+SELECT task_id, '[' || GROUP_CONCAT(blocker_id, ',' ORDER BY rowid) || ']' AS blockers
+FROM task_dependencies
+GROUP BY task_id;
@@ -0,0 +1,20 @@
+-- Tareas repetitivas: cada serie guarda su regla (subconjunto de RRULE) y las tareas que la
+-- componen apuntan a ella. Al completar la última tarea de una serie activa se crea la siguiente.
+
+CREATE TABLE IF NOT EXISTS task_recurrences (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Regla en forma canónica, p. ej. FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10
+    rule TEXT NOT NULL,
+    -- Vencimiento de la primera tarea; fija el día del mes y las semanas del intervalo.
+    starts_at TEXT NOT NULL,
+    -- Tareas creadas en la serie, para aplicar COUNT.
+    occurrences INTEGER NOT NULL DEFAULT 1,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE tasks ADD COLUMN recurrence_id INTEGER REFERENCES task_recurrences(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_tasks_recurrence_id ON tasks(recurrence_id);
@@ -0,0 +1,195 @@
+use chrono::Utc;
+
+use crate::{
+    auth::{personal_tokens::list_personal_tokens, sessions::{describe_device, list_sessions, revoke_user_sessions}},
+    auth::token_versions::bump_token_version,
+    error::{AppError, Result},
+    models::{AccountExport, LinkedIdentity, LoginHistoryEntry, Task, User, UserSession},
+    security::{
+        audit::{record_audit_event, AuditContext},
+        rate_limiter::normalize_attempt_email,
+    },
+    tasks::{TASK_COLUMNS, TASK_FROM},
+    AppState,
+};
+
+/// Reúne todos los datos personales de un usuario para entregárselos (derecho de acceso).
+pub async fn export_account_data(state: &AppState, user_id: i32, current_session: Option<&str>) -> Result<AccountExport> {
+    let profile: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Usuario con ID {} no encontrado", user_id)))?;
+
+    let tasks: Vec<Task> = sqlx::query_as(&format!("SELECT {} {} WHERE t.user_id = ? ORDER BY t.id", TASK_COLUMNS, TASK_FROM))
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let login_history: Vec<LoginHistoryEntry> = sqlx::query_as(
+        "SELECT ip_address, success, user_agent, attempted_at
+         FROM login_attempts WHERE email = ? ORDER BY id DESC"
+    )
+    .bind(normalize_attempt_email(&profile.email))
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let sessions = list_sessions(state, user_id)
+        .await?
+        .into_iter()
+        .map(|session| UserSession {
+            current: current_session == Some(session.id.as_str()),
+            device: describe_device(session.user_agent.as_deref()),
+            id: session.id,
+            ip_address: session.ip_address,
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+            last_active_at: session.last_active_at,
+        })
+        .collect();
+
+    let linked_identities: Vec<LinkedIdentity> = sqlx::query_as(
+        "SELECT issuer, subject, email, created_at, last_login_at
+         FROM user_identities WHERE user_id = ? ORDER BY id"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(AccountExport {
+        exported_at: Utc::now().to_rfc3339(),
+        profile,
+        tasks,
+        login_history,
+        sessions,
+        personal_access_tokens: list_personal_tokens(state, user_id).await?,
+        linked_identities,
+    })
+}
+
+/// Elimina definitivamente una cuenta (derecho de supresión).
+/// Las tareas, sesiones y tokens se borran en cascada; el historial de login se borra por email.
+/// No se permite eliminar al último administrador.
+pub async fn delete_account(state: &AppState, user_id: i32) -> Result<User> {
+    let mut tx = state.db_pool.begin().await?;
+
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Usuario con ID {} no encontrado", user_id)))?;
+
+    if user.role == "admin" {
+        let admins: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = 'admin'")
+            .fetch_one(&mut *tx)
+            .await?;
+        if admins <= 1 {
+            return Err(AppError::Conflict("No se puede eliminar al último administrador".to_string()));
+        }
+    }
+
+    sqlx::query("DELETE FROM login_attempts WHERE email = ?")
+        .bind(normalize_attempt_email(&user.email))
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM users WHERE id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    state.token_versions.remove(&user_id);
+    Ok(user)
+}
+
+/// Estados posibles de una cuenta. Solo las cuentas activas pueden iniciar sesión o usar la API.
+pub const ACCOUNT_ACTIVE: &str = "active";
+pub const ACCOUNT_SUSPENDED: &str = "suspended";
+pub const ACCOUNT_DEACTIVATED: &str = "deactivated";
+
+/// Rechaza las cuentas suspendidas o desactivadas.
+pub fn check_account_active(status: &str) -> Result<()> {
+    match status {
+        ACCOUNT_ACTIVE => Ok(()),
+        ACCOUNT_SUSPENDED => Err(AppError::Authentication("La cuenta está suspendida".to_string())),
+        _ => Err(AppError::Authentication("La cuenta está desactivada".to_string())),
+    }
+}
+
+/// Carga una cuenta y comprueba que sigue activa. Los JWT no lo necesitan (una suspensión
+/// sube la versión de token), pero los tokens personales no llevan el rol ni la versión.
+pub async fn load_active_user(state: &AppState, user_id: i32) -> Result<User> {
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::Authentication("Usuario no encontrado en la base de datos".to_string()))?;
+
+    check_account_active(&user.status)?;
+    Ok(user)
+}
+
+/// Cambia el estado de una cuenta. Al suspenderla o desactivarla se cierran todas sus sesiones;
+/// sus datos y tokens personales se conservan para cuando se reactive.
+/// No se permite dejar el sistema sin administradores activos.
+pub async fn set_account_status(
+    state: &AppState,
+    actor_id: i32,
+    user_id: i32,
+    status: &str,
+    reason: Option<&str>,
+) -> Result<User> {
+    if actor_id == user_id && status != ACCOUNT_ACTIVE {
+        return Err(AppError::BadRequest("No puedes suspender ni desactivar tu propia cuenta".to_string()));
+    }
+
+    let mut tx = state.db_pool.begin().await?;
+
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Usuario con ID {} no encontrado", user_id)))?;
+
+    if user.role == "admin" && status != ACCOUNT_ACTIVE {
+        let active_admins: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM users WHERE role = 'admin' AND status = 'active' AND id != ?"
+        )
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if active_admins == 0 {
+            return Err(AppError::Conflict("No se puede suspender al último administrador activo".to_string()));
+        }
+    }
+
+    let now = Utc::now().to_rfc3339();
+    sqlx::query("UPDATE users SET status = ?, status_reason = ?, status_changed_at = ? WHERE id = ?")
+        .bind(status)
+        .bind(reason)
+        .bind(&now)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    if status != ACCOUNT_ACTIVE {
+        revoke_user_sessions(state, user_id, None).await?;
+        bump_token_version(state, user_id).await?;
+    }
+
+    let action = match status {
+        ACCOUNT_ACTIVE => "user.reactivated",
+        ACCOUNT_SUSPENDED => "user.suspended",
+        _ => "user.deactivated",
+    };
+    record_audit_event(&state.db_pool, actor_id, user_id, action, AuditContext::default()).await?;
+
+    Ok(User {
+        status: status.to_string(),
+        status_reason: reason.map(|r| r.to_string()),
+        ..user
+    })
+}
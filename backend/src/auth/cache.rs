@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+/// Número máximo de entradas; al llenarse se descartan las caducadas o, si no basta, todas.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Caché en memoria con caducidad, compartida por todas las peticiones a través del `AppState`.
+/// Los cambios hechos por esta instancia la invalidan al momento; la caducidad acota el retraso
+/// con que se ven los hechos por otras instancias que comparten la base de datos.
+#[derive(Clone)]
+pub struct TtlCache<K, V> {
+    entries: Arc<RwLock<HashMap<K, (V, Instant)>>>,
+    ttl: Duration,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl_seconds: u64) -> Self {
+        Self {
+            entries: Arc::new(RwLock::new(HashMap::new())),
+            ttl: Duration::from_secs(ttl_seconds),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.read().ok()?;
+        entries
+            .get(key)
+            .filter(|(_, loaded_at)| loaded_at.elapsed() < self.ttl)
+            .map(|(value, _)| value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if let Ok(mut entries) = self.entries.write() {
+            if entries.len() >= MAX_ENTRIES {
+                let ttl = self.ttl;
+                entries.retain(|_, (_, loaded_at)| loaded_at.elapsed() < ttl);
+                if entries.len() >= MAX_ENTRIES {
+                    entries.clear();
+                }
+            }
+            entries.insert(key, (value, Instant::now()));
+        }
+    }
+
+    pub fn remove(&self, key: &K) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.remove(key);
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.clear();
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use axum::http::{header, HeaderMap, HeaderValue, Method};
+
+use crate::{
+    auth::opaque::generate_opaque_token,
+    config::Config,
+    error::{AppError, Result},
+};
+
+/// Cookie HttpOnly con el access token.
+pub const ACCESS_COOKIE: &str = "todo_access";
+/// Cookie HttpOnly con el refresh token.
+pub const REFRESH_COOKIE: &str = "todo_refresh";
+/// Cookie legible desde JavaScript con el token CSRF (patrón double-submit).
+pub const CSRF_COOKIE: &str = "todo_csrf";
+/// Cabecera en la que el frontend devuelve el valor de `CSRF_COOKIE`.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Valor de una cookie de la petición.
+pub fn read_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_string())
+}
+
+/// Exige que la cabecera `X-CSRF-Token` coincida con la cookie CSRF. Un sitio ajeno puede hacer
+/// que el navegador envíe las cookies, pero no puede leerlas para copiar su valor en la cabecera.
+pub fn check_csrf(headers: &HeaderMap) -> Result<()> {
+    let cookie = read_cookie(headers, CSRF_COOKIE);
+    let header = headers.get(CSRF_HEADER).and_then(|value| value.to_str().ok());
+
+    match (cookie, header) {
+        (Some(cookie), Some(header)) if !cookie.is_empty() && cookie == header => Ok(()),
+        _ => Err(AppError::Authentication("Token CSRF ausente o inválido".to_string())),
+    }
+}
+
+/// Rechaza las peticiones de sesión con cookies si el modo no está habilitado.
+pub fn ensure_cookies_enabled(config: &Config) -> Result<()> {
+    if !config.auth_cookies_enabled {
+        return Err(AppError::BadRequest("La autenticación con cookies no está habilitada".to_string()));
+    }
+    Ok(())
+}
+
+/// Los métodos que no modifican el estado no necesitan el token CSRF.
+pub fn requires_csrf(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Cabeceras `Set-Cookie` que abren una sesión con cookies. Devuelve también el token CSRF,
+/// que se incluye en la respuesta para los frontends que no pueden leer la cookie.
+pub fn session_cookies(config: &Config, access_token: &str, refresh_token: &str) -> Result<(HeaderMap, String)> {
+    ensure_cookies_enabled(config)?;
+
+    let csrf_token = generate_opaque_token();
+    let refresh_max_age = config.refresh_token_expiration_days * 24 * 60 * 60;
+
+    let mut headers = HeaderMap::new();
+    headers.append(header::SET_COOKIE, build_cookie(config, ACCESS_COOKIE, access_token, config.jwt_expiration_hours * 60 * 60, true)?);
+    headers.append(header::SET_COOKIE, build_cookie(config, REFRESH_COOKIE, refresh_token, refresh_max_age, true)?);
+    headers.append(header::SET_COOKIE, build_cookie(config, CSRF_COOKIE, &csrf_token, refresh_max_age, false)?);
+
+    Ok((headers, csrf_token))
+}
+
+/// Cabeceras `Set-Cookie` que borran las cookies de sesión.
+pub fn clear_session_cookies(config: &Config) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, http_only) in [(ACCESS_COOKIE, true), (REFRESH_COOKIE, true), (CSRF_COOKIE, false)] {
+        headers.append(header::SET_COOKIE, build_cookie(config, name, "", 0, http_only)?);
+    }
+    Ok(headers)
+}
+
+fn build_cookie(config: &Config, name: &str, value: &str, max_age: i64, http_only: bool) -> Result<HeaderValue> {
+    let mut cookie = format!(
+        "{}={}; Path=/; Max-Age={}; SameSite={}",
+        name, value, max_age, config.auth_cookie_same_site
+    );
+    if http_only {
+        cookie.push_str("; HttpOnly");
+    }
+    if config.auth_cookie_secure {
+        cookie.push_str("; Secure");
+    }
+    if let Some(domain) = &config.auth_cookie_domain {
+        cookie.push_str(&format!("; Domain={}", domain));
+    }
+
+    HeaderValue::from_str(&cookie)
+        .map_err(|_| AppError::InternalServerError("Cookie de sesión inválida".to_string()))
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    auth::opaque::{generate_opaque_token, hash_opaque_token},
+    error::{AppError, Result},
+    mailer::MailMessage,
+    models::User,
+    AppState,
+};
+
+#[derive(sqlx::FromRow, Debug)]
+struct EmailVerificationRow {
+    id: i64,
+    user_id: i32,
+    email: String,
+    expires_at: String,
+    used_at: Option<String>,
+}
+
+/// Emite un token de verificación para el email actual del usuario y se lo envía por correo.
+/// Los tokens pendientes anteriores quedan invalidados.
+pub async fn send_verification_email(state: &AppState, user: &User) -> Result<()> {
+    let token = generate_opaque_token();
+    let now = Utc::now();
+    let expires_at = now + Duration::hours(state.config.email_verification_expiration_hours);
+
+    let mut tx = state.db_pool.begin().await?;
+
+    sqlx::query("UPDATE email_verification_tokens SET used_at = ? WHERE user_id = ? AND used_at IS NULL")
+        .bind(now.to_rfc3339())
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO email_verification_tokens (user_id, email, token_hash, expires_at)
+         VALUES (?, ?, ?, ?)"
+    )
+    .bind(user.id)
+    .bind(&user.email)
+    .bind(hash_opaque_token(&token))
+    .bind(expires_at.to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let message = MailMessage {
+        to: user.email.clone(),
+        subject: "Verifica tu email".to_string(),
+        body: format!(
+            "Hola {},\n\nConfirma tu dirección de correo abriendo el siguiente enlace:\n{}/verify-email?token={}\n\nEl enlace caduca en {} horas.",
+            user.name, state.config.app_base_url, token, state.config.email_verification_expiration_hours
+        ),
+    };
+
+    if let Err(err) = state.mailer.send(message).await {
+        eprintln!("❌ Error enviando correo de verificación a {}: {}", user.email, err);
+    }
+
+    Ok(())
+}
+
+/// Consume un token de verificación y marca el email como verificado.
+/// Devuelve el ID del usuario verificado.
+pub async fn verify_email_token(state: &AppState, token: &str) -> Result<i32> {
+    let invalid = || AppError::BadRequest("Token de verificación inválido o expirado".to_string());
+
+    let row: EmailVerificationRow = sqlx::query_as(
+        "SELECT id, user_id, email, expires_at, used_at
+         FROM email_verification_tokens WHERE token_hash = ?"
+    )
+    .bind(hash_opaque_token(token))
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(invalid)?;
+
+    if row.used_at.is_some() {
+        return Err(invalid());
+    }
+
+    let expires_at = DateTime::parse_from_rfc3339(&row.expires_at)
+        .map_err(|_| AppError::InternalServerError("Error parsing verification token expiration".to_string()))?;
+    if Utc::now() >= expires_at {
+        return Err(invalid());
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let mut tx = state.db_pool.begin().await?;
+
+    let result = sqlx::query("UPDATE email_verification_tokens SET used_at = ? WHERE id = ? AND used_at IS NULL")
+        .bind(&now)
+        .bind(row.id)
+        .execute(&mut *tx)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(invalid());
+    }
+
+    // Solo se verifica si el email no cambió desde que se envió el token.
+    let result = sqlx::query("UPDATE users SET email_verified_at = ? WHERE id = ? AND email = ?")
+        .bind(&now)
+        .bind(row.user_id)
+        .bind(&row.email)
+        .execute(&mut *tx)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(invalid());
+    }
+
+    tx.commit().await?;
+
+    Ok(row.user_id)
+}
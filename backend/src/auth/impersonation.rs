@@ -0,0 +1,57 @@
+use chrono::{TimeZone, Utc};
+
+use crate::{
+    error::{AppError, Result},
+    models::{ImpersonationResponse, User},
+    security::audit::{record_audit_event, AuditContext, IMPERSONATION_STARTED},
+    security::permissions::load_role_permissions,
+    AppState,
+};
+
+/// Emite un token para que `impersonator_id` vea la API exactamente como `user_id`.
+/// Solo se pueden suplantar cuentas cuyo rol no concede permisos.
+pub async fn start_impersonation(
+    state: &AppState,
+    impersonator_id: i32,
+    user_id: i32,
+    ip_address: Option<&str>,
+) -> Result<ImpersonationResponse> {
+    if impersonator_id == user_id {
+        return Err(AppError::BadRequest("No puedes suplantar tu propia cuenta".to_string()));
+    }
+
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Usuario con ID {} no encontrado", user_id)))?;
+
+    if !load_role_permissions(&state.db_pool, &user.role).await?.is_empty() {
+        return Err(AppError::Authentication(
+            "No se pueden suplantar cuentas con permisos de administración".to_string(),
+        ));
+    }
+
+    let (token, expires_at) = state.jwt_service.generate_impersonation_token(
+        &user,
+        impersonator_id,
+        state.config.impersonation_token_minutes,
+    )?;
+
+    record_audit_event(
+        &state.db_pool,
+        impersonator_id,
+        user_id,
+        IMPERSONATION_STARTED,
+        AuditContext { ip_address, ..Default::default() },
+    )
+    .await?;
+
+    let expires_at = Utc
+        .timestamp_opt(expires_at, 0)
+        .single()
+        .unwrap_or_else(Utc::now)
+        .to_rfc3339();
+
+    Ok(ImpersonationResponse { token, expires_at, user })
+}
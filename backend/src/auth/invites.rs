@@ -0,0 +1,199 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::SqliteConnection;
+
+use crate::{
+    auth::opaque::{generate_opaque_token, hash_opaque_token},
+    error::{AppError, Result},
+    mailer::MailMessage,
+    models::Invite,
+    AppState,
+};
+
+/// Clave de `app_settings` que, si existe, sustituye a `INVITE_ONLY_REGISTRATION`.
+const INVITE_ONLY_SETTING: &str = "invite_only_registration";
+
+#[derive(sqlx::FromRow, Debug)]
+struct InviteRow {
+    id: i32,
+    code_prefix: String,
+    email: Option<String>,
+    role: String,
+    created_by: Option<i32>,
+    expires_at: String,
+    used_at: Option<String>,
+    used_by: Option<i32>,
+    revoked_at: Option<String>,
+    created_at: String,
+}
+
+impl InviteRow {
+    fn is_expired(&self) -> bool {
+        DateTime::parse_from_rfc3339(&self.expires_at).map_or(true, |expires_at| Utc::now() >= expires_at)
+    }
+
+    fn into_model(self) -> Invite {
+        let status = if self.used_at.is_some() {
+            "used"
+        } else if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.is_expired() {
+            "expired"
+        } else {
+            "pending"
+        };
+
+        Invite {
+            id: self.id,
+            code_prefix: self.code_prefix,
+            email: self.email,
+            role: self.role,
+            status: status.to_string(),
+            created_by: self.created_by,
+            expires_at: self.expires_at,
+            used_at: self.used_at,
+            used_by: self.used_by,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Indica si el registro público está cerrado. El ajuste en tiempo de ejecución
+/// tiene prioridad sobre la configuración.
+pub async fn is_invite_only_registration(state: &AppState) -> Result<bool> {
+    let setting: Option<String> = sqlx::query_scalar("SELECT value FROM app_settings WHERE key = ?")
+        .bind(INVITE_ONLY_SETTING)
+        .fetch_optional(&state.db_pool)
+        .await?;
+
+    Ok(setting
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(state.config.invite_only_registration))
+}
+
+/// Abre o cierra el registro público sin reiniciar el servidor.
+pub async fn set_invite_only_registration(state: &AppState, invite_only: bool) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO app_settings (key, value, updated_at) VALUES (?, ?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at"
+    )
+    .bind(INVITE_ONLY_SETTING)
+    .bind(invite_only.to_string())
+    .bind(Utc::now().to_rfc3339())
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Crea una invitación y devuelve el código en claro (solo se muestra una vez).
+/// Si se indica un email, solo esa dirección podrá usarla y se le envía por correo.
+pub async fn create_invite(
+    state: &AppState,
+    created_by: i32,
+    email: Option<&str>,
+    role: &str,
+    expires_in_days: Option<i64>,
+) -> Result<(String, Invite)> {
+    let code = generate_opaque_token();
+    let expires_in_days = expires_in_days.unwrap_or(state.config.invite_expiration_days);
+    let expires_at = Utc::now() + Duration::days(expires_in_days);
+
+    let invite_id = sqlx::query(
+        "INSERT INTO invites (code_hash, code_prefix, email, role, created_by, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(hash_opaque_token(&code))
+    .bind(&code[..8])
+    .bind(email)
+    .bind(role)
+    .bind(created_by)
+    .bind(expires_at.to_rfc3339())
+    .execute(&state.db_pool)
+    .await?
+    .last_insert_rowid();
+
+    let row: InviteRow = sqlx::query_as("SELECT * FROM invites WHERE id = ?")
+        .bind(invite_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    if let Some(email) = email {
+        let message = MailMessage {
+            to: email.to_string(),
+            subject: "Te han invitado a To-Do API".to_string(),
+            body: format!(
+                "Hola,\n\nHas recibido una invitación para crear tu cuenta. Regístrate desde el siguiente enlace:\n{}/register?invite={}\n\nLa invitación caduca en {} días.",
+                state.config.app_base_url, code, expires_in_days
+            ),
+        };
+        if let Err(err) = state.mailer.send(message).await {
+            eprintln!("❌ Error enviando invitación a {}: {}", email, err);
+        }
+    }
+
+    Ok((code, row.into_model()))
+}
+
+/// Lista todas las invitaciones, las más recientes primero.
+pub async fn list_invites(state: &AppState) -> Result<Vec<Invite>> {
+    let rows: Vec<InviteRow> = sqlx::query_as("SELECT * FROM invites ORDER BY id DESC")
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    Ok(rows.into_iter().map(InviteRow::into_model).collect())
+}
+
+/// Revoca una invitación pendiente.
+pub async fn revoke_invite(state: &AppState, invite_id: i32) -> Result<()> {
+    let row: InviteRow = sqlx::query_as("SELECT * FROM invites WHERE id = ?")
+        .bind(invite_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Invitación con ID {} no encontrada", invite_id)))?;
+
+    if row.used_at.is_some() {
+        return Err(AppError::Conflict("La invitación ya fue utilizada".to_string()));
+    }
+
+    sqlx::query("UPDATE invites SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ?")
+        .bind(Utc::now().to_rfc3339())
+        .bind(invite_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Reserva una invitación válida para `email` dentro de la transacción del registro.
+/// Devuelve `(invite_id, role)`; la invitación queda usada aunque haya peticiones concurrentes.
+pub async fn claim_invite(conn: &mut SqliteConnection, code: &str, email: &str) -> Result<(i32, String)> {
+    let invalid = || AppError::BadRequest("Invitación inválida o expirada".to_string());
+
+    let row: InviteRow = sqlx::query_as("SELECT * FROM invites WHERE code_hash = ?")
+        .bind(hash_opaque_token(code.trim()))
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(invalid)?;
+
+    let email_matches = row
+        .email
+        .as_deref()
+        .is_none_or(|invited| invited.eq_ignore_ascii_case(email));
+    if row.used_at.is_some() || row.revoked_at.is_some() || row.is_expired() || !email_matches {
+        return Err(invalid());
+    }
+
+    let result = sqlx::query(
+        "UPDATE invites SET used_at = ? WHERE id = ? AND used_at IS NULL AND revoked_at IS NULL"
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(row.id)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(invalid());
+    }
+
+    Ok((row.id, row.role))
+}
@@ -1,61 +1,187 @@
-use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
 // Importamos nuestro gestor de errores personalizado
+use crate::auth::keys::{delete_retired_keys, key_exists, load_key_records, load_public_jwks, store_new_key, SigningAlgorithm};
+use crate::auth::opaque::generate_opaque_token;
+use crate::config::Config;
 use crate::error::{AppError, Result};
+use crate::models::{Jwk, User};
+
+/// `kid` interno usado para el secreto compartido HS256 (los tokens HS256 no llevan `kid`).
+const SHARED_SECRET_KID: &str = "hs256";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // Subject (user_id)
     pub exp: i64,    // Expiration time
     pub iat: i64,    // Issued at
+    pub jti: String, // JWT ID (permite revocar el token en el logout)
+    // Session ID (permite cerrar la sesión desde otro dispositivo)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    // Impersonator (ID del administrador que suplanta al usuario `sub`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imp: Option<i32>,
+    // Rol del usuario al emitir el token (evita consultarlo en cada petición)
+    pub role: String,
+    // Versión de token del usuario; deja de ser válida al cambiar su rol o su estado
+    pub ver: i64,
+    // Si el usuario tenía activada la autenticación en dos pasos al emitir el token
+    pub mfa: bool,
 }
 
+// Conjunto de claves en uso: una clave de firma y todas las que siguen siendo válidas para verificar.
+struct KeyRing {
+    signing_kid: Option<String>,
+    signing_created_at: chrono::DateTime<Utc>,
+    encoding_key: EncodingKey,
+    decoding_keys: HashMap<String, DecodingKey>,
+}
 
 // Esta es la única definición del struct, y es clonable
 // para poder ser parte del AppState.
 #[derive(Clone)]
 pub struct JwtService {
-    encoding_key: EncodingKey,
-    decoding_key: DecodingKey,
+    algorithm: SigningAlgorithm,
+    keys: Arc<RwLock<KeyRing>>,
     expiration_hours: i64,
+    rotation_hours: i64,
 }
 
 impl JwtService {
+    /// Crea un servicio HS256 basado en un secreto compartido.
     pub fn new(secret: &str, expiration_hours: i64) -> Self {
+        let mut decoding_keys = HashMap::new();
+        decoding_keys.insert(SHARED_SECRET_KID.to_string(), DecodingKey::from_secret(secret.as_ref()));
+
         Self {
-            encoding_key: EncodingKey::from_secret(secret.as_ref()),
-            decoding_key: DecodingKey::from_secret(secret.as_ref()),
+            algorithm: SigningAlgorithm::Hs256,
+            keys: Arc::new(RwLock::new(KeyRing {
+                signing_kid: None,
+                signing_created_at: Utc::now(),
+                encoding_key: EncodingKey::from_secret(secret.as_ref()),
+                decoding_keys,
+            })),
             expiration_hours,
+            rotation_hours: 0,
         }
     }
 
-    /// Genera un nuevo token JWT para un ID de usuario.
-    pub fn generate_token(&self, user_id: i32) -> Result<String> {
+    /// Crea el servicio según la configuración. Con RS256/EdDSA las claves se guardan
+    /// en la base de datos y se genera una nueva si no hay ninguna o si toca rotarla.
+    /// Varias instancias pueden compartir la base de datos: todas firman con la misma clave.
+    pub async fn from_config(config: &Config, db_pool: &SqlitePool) -> Result<Self> {
+        if !config.jwt_algorithm.is_asymmetric() {
+            return Ok(Self::new(&config.jwt_secret, config.jwt_expiration_hours));
+        }
+
+        let service = Self {
+            algorithm: config.jwt_algorithm,
+            keys: Arc::new(RwLock::new(KeyRing {
+                signing_kid: None,
+                signing_created_at: Utc::now(),
+                encoding_key: EncodingKey::from_secret(&[]),
+                decoding_keys: HashMap::new(),
+            })),
+            expiration_hours: config.jwt_expiration_hours,
+            rotation_hours: config.jwt_key_rotation_hours,
+        };
+
+        service.rotate_keys_if_due(db_pool).await?;
+
+        Ok(service)
+    }
+
+    /// Genera un nuevo token JWT para un usuario, ligado a su sesión si se indica.
+    pub fn generate_token(&self, user: &User, session_id: Option<&str>) -> Result<String> {
         let now = Utc::now();
         let exp = now + Duration::hours(self.expiration_hours);
 
         let claims = Claims {
-            sub: user_id.to_string(),
+            sub: user.id.to_string(),
+            exp: exp.timestamp(),
+            iat: now.timestamp(),
+            jti: generate_opaque_token(),
+            sid: session_id.map(|sid| sid.to_string()),
+            imp: None,
+            role: user.role.clone(),
+            ver: user.token_version,
+            mfa: user.totp_enabled_at.is_some(),
+        };
+
+        self.sign(&claims)
+    }
+
+    /// Genera un token de corta duración para actuar como `user` en nombre de `impersonator_id`.
+    /// No pertenece a ninguna sesión ni tiene refresh token. Devuelve el token y su expiración.
+    pub fn generate_impersonation_token(&self, user: &User, impersonator_id: i32, minutes: i64) -> Result<(String, i64)> {
+        let now = Utc::now();
+        let exp = now + Duration::minutes(minutes);
+
+        let claims = Claims {
+            sub: user.id.to_string(),
             exp: exp.timestamp(),
             iat: now.timestamp(),
+            jti: generate_opaque_token(),
+            sid: None,
+            imp: Some(impersonator_id),
+            role: user.role.clone(),
+            ver: user.token_version,
+            mfa: user.totp_enabled_at.is_some(),
         };
 
-        
+        Ok((self.sign(&claims)?, claims.exp))
+    }
+
+    fn sign(&self, claims: &Claims) -> Result<String> {
+        let keys = self.read_keys()?;
+        let mut header = Header::new(self.algorithm.jwt_algorithm());
+        header.kid = keys.signing_kid.clone();
+
         // El '?' al final convierte automáticamente el error de `encode` en nuestro AppError::Jwt.
-        let token = encode(&Header::default(), &claims, &self.encoding_key)?;
+        let token = encode(&header, claims, &keys.encoding_key)?;
 
         Ok(token)
     }
 
     /// Valida un token y devuelve sus datos si es correcto.
+    /// La clave de verificación se elige según el `kid` de la cabecera.
     pub fn validate_token(&self, token: &str) -> Result<TokenData<Claims>> {
+        let header = decode_header(token)?;
+        let kid = header.kid.as_deref().unwrap_or(SHARED_SECRET_KID);
+
+        let keys = self.read_keys()?;
+        let decoding_key = keys
+            .decoding_keys
+            .get(kid)
+            .ok_or_else(|| AppError::Authentication("Clave de firma desconocida".to_string()))?;
+
         // El '?' convierte el error si la decodificación falla.
-        let token_data = decode::<Claims>(token, &self.decoding_key, &Validation::default())?;
+        let validation = Validation::new(self.algorithm.jwt_algorithm());
+        let token_data = decode::<Claims>(token, decoding_key, &validation)?;
         Ok(token_data)
     }
 
+    /// Como `validate_token`, pero si el `kid` no está entre las claves cargadas y existe en la
+    /// base de datos (otra instancia acaba de rotar), recarga las claves antes de validar.
+    pub async fn validate_token_or_reload(&self, token: &str, db_pool: &SqlitePool) -> Result<TokenData<Claims>> {
+        if self.algorithm.is_asymmetric() {
+            if let Some(kid) = decode_header(token)?.kid {
+                let known = self.read_keys()?.decoding_keys.contains_key(&kid);
+                if !known && key_exists(db_pool, self.algorithm, &kid).await? {
+                    self.reload_keys(db_pool).await?;
+                }
+            }
+        }
+
+        self.validate_token(token)
+    }
+
     /// Extrae el ID de usuario de un token válido.
     pub fn extract_user_id(&self, token: &str) -> Result<i32> {
         let token_data = self.validate_token(token)?;
@@ -65,4 +191,114 @@ impl JwtService {
             .parse::<i32>()
             .map_err(|_| AppError::Authentication("ID de usuario inválido en el token".to_string()))
     }
-}
\ No newline at end of file
+
+    /// Claves públicas vigentes, para publicar en `/.well-known/jwks.json`.
+    /// Se leen de la base de datos para incluir las de todas las instancias.
+    pub async fn jwks(&self, db_pool: &SqlitePool) -> Result<Vec<Jwk>> {
+        if !self.algorithm.is_asymmetric() {
+            return Ok(Vec::new());
+        }
+
+        load_public_jwks(db_pool, self.algorithm, self.retired_keys_cutoff()).await
+    }
+
+    /// `kid` de la clave con la que se firman los tokens nuevos.
+    pub fn current_kid(&self) -> Option<String> {
+        self.read_keys().ok().and_then(|keys| keys.signing_kid.clone())
+    }
+
+    /// Rota la clave de firma si no hay ninguna o si la más reciente de la base de datos
+    /// superó `JWT_KEY_ROTATION_HOURS`. Antes recarga las claves, por si otra instancia ya rotó.
+    pub async fn rotate_keys_if_due(&self, db_pool: &SqlitePool) -> Result<bool> {
+        if !self.algorithm.is_asymmetric() {
+            return Ok(false);
+        }
+
+        self.reload_keys(db_pool).await?;
+
+        // Sin rotación configurada solo se genera una clave si todavía no hay ninguna.
+        let due_before = if self.rotation_hours > 0 {
+            Utc::now() - Duration::hours(self.rotation_hours)
+        } else {
+            DateTime::UNIX_EPOCH
+        };
+        let due = {
+            let keys = self.read_keys()?;
+            keys.signing_kid.is_none() || keys.signing_created_at <= due_before
+        };
+        if !due {
+            return Ok(false);
+        }
+
+        let rotated = store_new_key(db_pool, self.algorithm, due_before).await?.is_some();
+        self.reload_keys(db_pool).await?;
+        Ok(rotated)
+    }
+
+    /// Genera una nueva clave de firma. Las anteriores se siguen aceptando (y publicando)
+    /// hasta que expire el último token que pudieron firmar.
+    pub async fn rotate_keys(&self, db_pool: &SqlitePool) -> Result<()> {
+        if !self.algorithm.is_asymmetric() {
+            return Ok(());
+        }
+
+        store_new_key(db_pool, self.algorithm, Utc::now()).await?;
+        self.reload_keys(db_pool).await
+    }
+
+    // Las claves retiradas antes de este momento ya no pueden verificar ningún token vigente.
+    fn retired_keys_cutoff(&self) -> DateTime<Utc> {
+        Utc::now() - Duration::hours(self.expiration_hours)
+    }
+
+    async fn reload_keys(&self, db_pool: &SqlitePool) -> Result<()> {
+        delete_retired_keys(db_pool, self.retired_keys_cutoff()).await?;
+
+        let mut signing = None;
+        let mut decoding_keys = HashMap::new();
+
+        for record in load_key_records(db_pool, self.algorithm).await? {
+            let key = record.load(self.algorithm)?;
+            decoding_keys.insert(key.kid.clone(), key.decoding_key);
+            if !key.retired && signing.is_none() {
+                signing = Some((key.kid, key.created_at, key.encoding_key));
+            }
+        }
+
+        let mut keys = self
+            .keys
+            .write()
+            .map_err(|_| AppError::InternalServerError("Conjunto de claves JWT inconsistente".to_string()))?;
+        if let Some((kid, created_at, encoding_key)) = signing {
+            keys.signing_kid = Some(kid);
+            keys.signing_created_at = created_at;
+            keys.encoding_key = encoding_key;
+        }
+        keys.decoding_keys = decoding_keys;
+
+        Ok(())
+    }
+
+    fn read_keys(&self) -> Result<std::sync::RwLockReadGuard<'_, KeyRing>> {
+        self.keys
+            .read()
+            .map_err(|_| AppError::InternalServerError("Conjunto de claves JWT inconsistente".to_string()))
+    }
+}
+
+/// Lanza una tarea en segundo plano que rota las claves de firma según la configuración.
+pub fn spawn_key_rotation(jwt_service: JwtService, db_pool: SqlitePool) {
+    if !jwt_service.algorithm.is_asymmetric() || jwt_service.rotation_hours <= 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(10 * 60));
+        loop {
+            interval.tick().await;
+            if let Err(err) = jwt_service.rotate_keys_if_due(&db_pool).await {
+                eprintln!("❌ Error rotando claves JWT: {}", err);
+            }
+        }
+    });
+}
@@ -0,0 +1,277 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::pkcs8::{EncodePrivateKey, KeypairBytes};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use rand::rngs::OsRng;
+use rsa::{pkcs8::LineEnding, traits::PublicKeyParts, RsaPrivateKey};
+use sqlx::SqlitePool;
+
+use crate::{
+    auth::opaque::generate_opaque_token,
+    error::{AppError, Result},
+    models::Jwk,
+};
+
+/// Algoritmo con el que se firman los access tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SigningAlgorithm {
+    /// Secreto compartido (`JWT_SECRET`). No publica claves en el JWKS.
+    Hs256,
+    Rs256,
+    EdDsa,
+}
+
+impl SigningAlgorithm {
+    pub fn from_string(algorithm: &str) -> Option<Self> {
+        match algorithm.to_uppercase().as_str() {
+            "HS256" => Some(SigningAlgorithm::Hs256),
+            "RS256" => Some(SigningAlgorithm::Rs256),
+            "EDDSA" => Some(SigningAlgorithm::EdDsa),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningAlgorithm::Hs256 => "HS256",
+            SigningAlgorithm::Rs256 => "RS256",
+            SigningAlgorithm::EdDsa => "EdDSA",
+        }
+    }
+
+    pub fn is_asymmetric(&self) -> bool {
+        *self != SigningAlgorithm::Hs256
+    }
+
+    pub fn jwt_algorithm(&self) -> Algorithm {
+        match self {
+            SigningAlgorithm::Hs256 => Algorithm::HS256,
+            SigningAlgorithm::Rs256 => Algorithm::RS256,
+            SigningAlgorithm::EdDsa => Algorithm::EdDSA,
+        }
+    }
+}
+
+/// Fila de la tabla `jwt_signing_keys`.
+#[derive(sqlx::FromRow, Debug)]
+pub struct SigningKeyRecord {
+    pub kid: String,
+    pub private_key_pem: String,
+    pub public_jwk: String,
+    pub created_at: String,
+    pub retired_at: Option<String>,
+}
+
+/// Clave asimétrica lista para firmar y verificar.
+pub struct LoadedKey {
+    pub kid: String,
+    pub encoding_key: EncodingKey,
+    pub decoding_key: DecodingKey,
+    pub created_at: DateTime<Utc>,
+    pub retired: bool,
+}
+
+impl SigningKeyRecord {
+    pub fn load(&self, algorithm: SigningAlgorithm) -> Result<LoadedKey> {
+        let jwk: Jwk = serde_json::from_str(&self.public_jwk)
+            .map_err(|e| AppError::InternalServerError(format!("JWK inválido para la clave {}: {}", self.kid, e)))?;
+
+        let (encoding_key, decoding_key) = match algorithm {
+            SigningAlgorithm::Rs256 => (
+                EncodingKey::from_rsa_pem(self.private_key_pem.as_bytes())?,
+                DecodingKey::from_rsa_components(
+                    jwk.n.as_deref().unwrap_or_default(),
+                    jwk.e.as_deref().unwrap_or_default(),
+                )?,
+            ),
+            SigningAlgorithm::EdDsa => (
+                EncodingKey::from_ed_pem(self.private_key_pem.as_bytes())?,
+                DecodingKey::from_ed_components(jwk.x.as_deref().unwrap_or_default())?,
+            ),
+            SigningAlgorithm::Hs256 => {
+                return Err(AppError::InternalServerError(
+                    "HS256 no usa claves almacenadas".to_string(),
+                ))
+            }
+        };
+
+        Ok(LoadedKey {
+            kid: self.kid.clone(),
+            encoding_key,
+            decoding_key,
+            created_at: parse_timestamp(&self.created_at)?,
+            retired: self.retired_at.is_some(),
+        })
+    }
+}
+
+/// Genera un nuevo par de claves para el algoritmo indicado.
+/// Devuelve la clave privada en PEM (PKCS#8) y la clave pública en formato JWK.
+pub fn generate_key(algorithm: SigningAlgorithm) -> Result<(String, Jwk)> {
+    let kid = generate_opaque_token()[..16].to_string();
+
+    match algorithm {
+        SigningAlgorithm::EdDsa => {
+            let signing_key = ed25519_dalek::SigningKey::generate(&mut OsRng);
+            // PKCS#8 v1 (sin clave pública embebida): es el formato que acepta `ring`.
+            let keypair = KeypairBytes { secret_key: signing_key.to_bytes(), public_key: None };
+            let pem = keypair
+                .to_pkcs8_pem(LineEnding::LF)
+                .map_err(|e| AppError::InternalServerError(format!("Error generando clave EdDSA: {}", e)))?;
+
+            Ok((
+                pem.to_string(),
+                Jwk {
+                    kty: "OKP".to_string(),
+                    kid,
+                    key_use: "sig".to_string(),
+                    alg: algorithm.as_str().to_string(),
+                    n: None,
+                    e: None,
+                    crv: Some("Ed25519".to_string()),
+                    x: Some(URL_SAFE_NO_PAD.encode(signing_key.verifying_key().to_bytes())),
+                },
+            ))
+        }
+        SigningAlgorithm::Rs256 => {
+            let private_key = RsaPrivateKey::new(&mut OsRng, 2048)
+                .map_err(|e| AppError::InternalServerError(format!("Error generando clave RSA: {}", e)))?;
+            let pem = rsa::pkcs8::EncodePrivateKey::to_pkcs8_pem(&private_key, LineEnding::LF)
+                .map_err(|e| AppError::InternalServerError(format!("Error codificando clave RSA: {}", e)))?;
+
+            Ok((
+                pem.to_string(),
+                Jwk {
+                    kty: "RSA".to_string(),
+                    kid,
+                    key_use: "sig".to_string(),
+                    alg: algorithm.as_str().to_string(),
+                    n: Some(URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be())),
+                    e: Some(URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be())),
+                    crv: None,
+                    x: None,
+                },
+            ))
+        }
+        SigningAlgorithm::Hs256 => Err(AppError::InternalServerError(
+            "HS256 no usa pares de claves".to_string(),
+        )),
+    }
+}
+
+/// Carga las claves del algoritmo indicado, de la más reciente a la más antigua.
+pub async fn load_key_records(db_pool: &SqlitePool, algorithm: SigningAlgorithm) -> Result<Vec<SigningKeyRecord>> {
+    let records = sqlx::query_as::<_, SigningKeyRecord>(
+        "SELECT kid, private_key_pem, public_jwk, created_at, retired_at
+         FROM jwt_signing_keys
+         WHERE algorithm = ?
+         ORDER BY datetime(created_at) DESC"
+    )
+    .bind(algorithm.as_str())
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(records)
+}
+
+/// Claves públicas que siguen sirviendo para verificar: la de firma y las retiradas después de `cutoff`.
+/// Se leen de la base de datos para publicar también las que haya generado otra instancia.
+pub async fn load_public_jwks(db_pool: &SqlitePool, algorithm: SigningAlgorithm, cutoff: DateTime<Utc>) -> Result<Vec<Jwk>> {
+    let public_jwks: Vec<(String, String)> = sqlx::query_as(
+        "SELECT kid, public_jwk
+         FROM jwt_signing_keys
+         WHERE algorithm = ? AND (retired_at IS NULL OR datetime(retired_at) > datetime(?))
+         ORDER BY datetime(created_at) DESC"
+    )
+    .bind(algorithm.as_str())
+    .bind(cutoff.to_rfc3339())
+    .fetch_all(db_pool)
+    .await?;
+
+    public_jwks
+        .into_iter()
+        .map(|(kid, public_jwk)| {
+            serde_json::from_str(&public_jwk)
+                .map_err(|e| AppError::InternalServerError(format!("JWK inválido para la clave {}: {}", kid, e)))
+        })
+        .collect()
+}
+
+/// Indica si existe una clave con ese `kid` (p. ej. recién generada por otra instancia).
+pub async fn key_exists(db_pool: &SqlitePool, algorithm: SigningAlgorithm, kid: &str) -> Result<bool> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM jwt_signing_keys WHERE kid = ? AND algorithm = ?)")
+        .bind(kid)
+        .bind(algorithm.as_str())
+        .fetch_one(db_pool)
+        .await?;
+
+    Ok(exists)
+}
+
+/// Guarda una nueva clave de firma y retira las anteriores, salvo que ya haya una activa
+/// creada después de `due_before` (p. ej. porque otra instancia acaba de rotarla).
+/// La comprobación va en el mismo `INSERT`, así que dos instancias nunca rotan a la vez.
+/// Devuelve el `kid` de la clave nueva, o `None` si no hacía falta rotar.
+pub async fn store_new_key(db_pool: &SqlitePool, algorithm: SigningAlgorithm, due_before: DateTime<Utc>) -> Result<Option<String>> {
+    let (private_key_pem, jwk) = generate_key(algorithm)?;
+    let public_jwk = serde_json::to_string(&jwk)
+        .map_err(|e| AppError::InternalServerError(format!("Error serializando JWK: {}", e)))?;
+    let now = Utc::now().to_rfc3339();
+
+    let mut tx = db_pool.begin().await?;
+
+    let inserted = sqlx::query(
+        "INSERT INTO jwt_signing_keys (kid, algorithm, private_key_pem, public_jwk, created_at)
+         SELECT ?, ?, ?, ?, ?
+         WHERE NOT EXISTS (
+             SELECT 1 FROM jwt_signing_keys
+             WHERE algorithm = ? AND retired_at IS NULL AND datetime(created_at) > datetime(?)
+         )"
+    )
+    .bind(&jwk.kid)
+    .bind(algorithm.as_str())
+    .bind(&private_key_pem)
+    .bind(&public_jwk)
+    .bind(&now)
+    .bind(algorithm.as_str())
+    .bind(due_before.to_rfc3339())
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if inserted == 0 {
+        return Ok(None);
+    }
+
+    sqlx::query("UPDATE jwt_signing_keys SET retired_at = ? WHERE algorithm = ? AND retired_at IS NULL AND kid != ?")
+        .bind(&now)
+        .bind(algorithm.as_str())
+        .bind(&jwk.kid)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    println!("->> AUTH | Nueva clave de firma {} generada (kid: {})", algorithm.as_str(), jwk.kid);
+    Ok(Some(jwk.kid))
+}
+
+/// Elimina las claves retiradas antes de `cutoff`: ningún token firmado con ellas sigue vigente.
+pub async fn delete_retired_keys(db_pool: &SqlitePool, cutoff: DateTime<Utc>) -> Result<u64> {
+    let deleted = sqlx::query(
+        "DELETE FROM jwt_signing_keys
+         WHERE retired_at IS NOT NULL AND datetime(retired_at) <= datetime(?)"
+    )
+    .bind(cutoff.to_rfc3339())
+    .execute(db_pool)
+    .await?
+    .rows_affected();
+
+    Ok(deleted)
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|_| AppError::InternalServerError(format!("Fecha inválida: {}", value)))
+}
@@ -1,15 +1,58 @@
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{ConnectInfo, FromRequestParts, OriginalUri},
     http::request::Parts,
 };
-use crate::{error::AppError, AppState};
+use std::net::SocketAddr;
+use crate::{
+    auth::account::load_active_user,
+    auth::cookies::{check_csrf, read_cookie, requires_csrf, ACCESS_COOKIE},
+    auth::personal_tokens::{authenticate_personal_token, check_scope, check_session, TokenScope, PERSONAL_TOKEN_PREFIX},
+    auth::revocation::is_jti_revoked,
+    auth::sessions::touch_session,
+    auth::token_versions::current_token_version,
+    error::AppError,
+    security::audit::{record_audit_event, AuditContext, IMPERSONATED_REQUEST},
+    security::get_real_ip,
+    AppState,
+};
+
+// Marca la petición como ya auditada: varios extractores pueden autenticarla.
+#[derive(Clone)]
+struct ImpersonationAudited;
 
 // El extractor que valida el JWT y devuelve el ID del usuario.
 // Se puede usar en cualquier handler que requiera autenticación.
 #[derive(Debug)]
 pub struct AuthenticatedUser {
     pub user_id: i32,
+    /// Rol del usuario, tomado del JWT (o de la base de datos para los tokens personales).
+    pub role: String,
+    /// Si el usuario tiene activada la autenticación en dos pasos.
+    pub two_factor_enabled: bool,
+    /// Identificador único del token usado en la petición.
+    pub jti: String,
+    /// Expiración del token (timestamp UNIX).
+    pub token_exp: i64,
+    /// Sesión (dispositivo) a la que pertenece el token, si la tiene.
+    pub session_id: Option<String>,
+    /// Scopes concedidos si se autenticó con un token personal; `None` para sesiones con JWT.
+    pub scopes: Option<Vec<TokenScope>>,
+    /// Administrador que suplanta al usuario, si el token es de suplantación.
+    pub impersonator_id: Option<i32>,
+}
+
+impl AuthenticatedUser {
+    /// Exige que las credenciales incluyan `scope` (las sesiones con JWT lo incluyen todo).
+    pub fn require_scope(&self, scope: TokenScope) -> Result<(), AppError> {
+        check_scope(&self.scopes, scope)
+    }
+
+    /// Exige una sesión propia con JWT: ni un token personal ni una suplantación.
+    pub fn require_session(&self) -> Result<(), AppError> {
+        check_session(&self.scopes)?;
+        check_not_impersonated(self.impersonator_id)
+    }
 }
 
 #[async_trait]
@@ -24,18 +67,109 @@ impl FromRequestParts<AppState> for AuthenticatedUser {
         let headers = parts.headers.clone();
         let auth_header = headers
             .get("Authorization")
-            .and_then(|value| value.to_str().ok())
-            .ok_or_else(|| AppError::Authentication("Missing Authorization header".to_string()))?;
+            .and_then(|value| value.to_str().ok());
+
+        // 2. Verificar que el header tiene el formato "Bearer <token>". Sin header, se acepta
+        //    la cookie de sesión; al enviarla el navegador por sí solo, las peticiones que
+        //    modifican datos deben incluir además el token CSRF.
+        let bearer_token = match auth_header {
+            Some(auth_header) => auth_header
+                .strip_prefix("Bearer ")
+                .ok_or_else(|| AppError::Authentication("Invalid token format".to_string()))?
+                .to_string(),
+            None => {
+                let cookie = read_cookie(&headers, ACCESS_COOKIE)
+                    .filter(|_| state.config.auth_cookies_enabled)
+                    .ok_or_else(|| AppError::Authentication("Missing Authorization header".to_string()))?;
+                if requires_csrf(&parts.method) {
+                    check_csrf(&headers)?;
+                }
+                cookie
+            }
+        };
+        let bearer_token = bearer_token.as_str();
 
-        // 2. Verificar que el header tiene el formato "Bearer <token>"
-        let bearer_token = auth_header
-            .strip_prefix("Bearer ")
-            .ok_or_else(|| AppError::Authentication("Invalid token format".to_string()))?;
+        // 3. Los tokens personales no son JWT: se validan contra la base de datos
+        if bearer_token.starts_with(PERSONAL_TOKEN_PREFIX) {
+            let (token_id, user_id, scopes) = authenticate_personal_token(state, bearer_token).await?;
+            let user = load_active_user(state, user_id).await?;
+            return Ok(AuthenticatedUser {
+                user_id,
+                role: user.role,
+                two_factor_enabled: user.totp_enabled_at.is_some(),
+                jti: format!("pat-{}", token_id),
+                token_exp: 0,
+                session_id: None,
+                scopes: Some(scopes),
+                impersonator_id: None,
+            });
+        }
 
-        // 3. Decodificar y validar el token usando el servicio JWT
-        let token_data = state.jwt_service.validate_token(bearer_token)?;
+        // 4. Decodificar y validar el token usando el servicio JWT
+        let token_data = state.jwt_service.validate_token_or_reload(bearer_token, &state.db_pool).await?;
+
+        // 5. Rechazar tokens revocados mediante logout
+        if is_jti_revoked(state, &token_data.claims.jti).await? {
+            return Err(AppError::Authentication("Token revocado".to_string()));
+        }
+
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| get_real_ip(addr, &headers));
+        let user_id: i32 = token_data.claims.sub.parse().unwrap();
+
+        // Un cambio de rol, una suspensión o el borrado de la cuenta suben la versión de token,
+        // así que los tokens anteriores dejan de funcionar de inmediato sin consultar el rol.
+        if current_token_version(state, user_id).await? != token_data.claims.ver {
+            return Err(AppError::Authentication("Token obsoleto, vuelve a iniciar sesión".to_string()));
+        }
+
+        // 6. Rechazar tokens de sesiones cerradas y registrar la actividad
+        if let Some(session_id) = &token_data.claims.sid {
+            touch_session(state, session_id, ip.as_deref()).await?;
+        }
+
+        // 7. Cada petición hecha suplantando a un usuario queda en el registro de auditoría
+        if let Some(impersonator_id) = token_data.claims.imp {
+            if parts.extensions.get::<ImpersonationAudited>().is_none() {
+                let path = parts
+                    .extensions
+                    .get::<OriginalUri>()
+                    .map(|OriginalUri(uri)| uri.path().to_string())
+                    .unwrap_or_else(|| parts.uri.path().to_string());
+                let context = AuditContext {
+                    method: Some(parts.method.as_str()),
+                    path: Some(&path),
+                    ip_address: ip.as_deref(),
+                };
+                record_audit_event(&state.db_pool, impersonator_id, user_id, IMPERSONATED_REQUEST, context).await?;
+                parts.extensions.insert(ImpersonationAudited);
+            }
+            println!("->> MIDDLEWARE | Petición suplantada: usuario {} por admin {}", user_id, impersonator_id);
+        }
+
+        // 8. Devolver el usuario autenticado
+        Ok(AuthenticatedUser {
+            user_id,
+            role: token_data.claims.role,
+            two_factor_enabled: token_data.claims.mfa,
+            jti: token_data.claims.jti,
+            token_exp: token_data.claims.exp,
+            session_id: token_data.claims.sid,
+            scopes: None,
+            impersonator_id: token_data.claims.imp,
+        })
+    }
+}
 
-        // 4. Devolver el usuario autenticado
-        Ok(AuthenticatedUser { user_id: token_data.claims.sub.parse().unwrap() })
+/// Las acciones sensibles (contraseña, 2FA, tokens, sesiones, cuenta) quedan fuera del
+/// alcance de una suplantación.
+pub fn check_not_impersonated(impersonator_id: Option<i32>) -> Result<(), AppError> {
+    if impersonator_id.is_some() {
+        return Err(AppError::Authentication(
+            "Esta operación no está permitida mientras se suplanta a un usuario".to_string(),
+        ));
     }
+    Ok(())
 }
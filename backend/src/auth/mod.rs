@@ -1,5 +1,22 @@
+pub mod account;
+pub mod cache;
+pub mod cookies;
+pub mod email_verification;
+pub mod impersonation;
+pub mod invites;
 pub mod jwt;
+pub mod keys;
 pub mod middleware;
+pub mod oidc;
+pub mod opaque;
+pub mod password_reset;
+pub mod passwords;
+pub mod personal_tokens;
+pub mod refresh;
+pub mod revocation;
+pub mod sessions;
+pub mod token_versions;
+pub mod two_factor;
 
 pub use jwt::*;
 pub use middleware::AuthenticatedUser;
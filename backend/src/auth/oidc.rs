@@ -0,0 +1,370 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    auth::{
+        invites::is_invite_only_registration,
+        opaque::{generate_opaque_token, hash_opaque_token},
+        passwords::hash_password,
+        token_versions::bump_token_version,
+    },
+    config::Config,
+    error::{AppError, Result},
+    models::User,
+    AppState,
+};
+
+/// Validez de una petición de autorización pendiente.
+const LOGIN_STATE_EXPIRATION_MINUTES: i64 = 10;
+
+/// Campos del documento de descubrimiento (`/.well-known/openid-configuration`) que se usan.
+#[derive(Deserialize, Debug)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(sqlx::FromRow, Debug)]
+struct LoginStateRow {
+    id: i64,
+    code_verifier: String,
+    nonce: String,
+    expires_at: String,
+    used_at: Option<String>,
+}
+
+/// Identidad verificada a partir del ID token del proveedor.
+#[derive(Debug)]
+pub struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub groups: Vec<String>,
+}
+
+struct OidcClient<'a> {
+    config: &'a Config,
+    issuer_url: &'a str,
+    client_id: &'a str,
+    http: reqwest::Client,
+}
+
+impl<'a> OidcClient<'a> {
+    fn from_config(config: &'a Config) -> Result<Self> {
+        match (&config.oidc_issuer_url, &config.oidc_client_id) {
+            (Some(issuer_url), Some(client_id)) => Ok(Self {
+                config,
+                issuer_url: issuer_url.trim_end_matches('/'),
+                client_id,
+                http: reqwest::Client::new(),
+            }),
+            _ => Err(AppError::BadRequest("El inicio de sesión con OpenID Connect no está configurado".to_string())),
+        }
+    }
+
+    fn redirect_url(&self) -> String {
+        self.config
+            .oidc_redirect_url
+            .clone()
+            .unwrap_or_else(|| format!("{}/auth/oidc/callback", self.config.app_base_url))
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata> {
+        let url = format!("{}/.well-known/openid-configuration", self.issuer_url);
+        let metadata: ProviderMetadata = self.get_json(&url).await?;
+
+        if metadata.issuer.trim_end_matches('/') != self.issuer_url {
+            return Err(AppError::InternalServerError(format!(
+                "El emisor del proveedor ({}) no coincide con OIDC_ISSUER_URL",
+                metadata.issuer
+            )));
+        }
+        Ok(metadata)
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T> {
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| AppError::InternalServerError(format!("Error contactando al proveedor OIDC: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Respuesta inválida del proveedor OIDC: {}", e)))
+    }
+}
+
+/// Inicia el flujo authorization code + PKCE. Guarda el `state`, el verificador PKCE y el
+/// nonce, y devuelve la URL del proveedor a la que se debe redirigir al usuario junto con el `state`.
+pub async fn begin_oidc_login(state: &AppState) -> Result<(String, String)> {
+    let client = OidcClient::from_config(&state.config)?;
+    let metadata = client.metadata().await?;
+
+    let login_state = generate_opaque_token();
+    let nonce = generate_opaque_token();
+    let code_verifier = URL_SAFE_NO_PAD.encode(hex::decode(generate_opaque_token()).unwrap_or_default());
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+    sqlx::query(
+        "INSERT INTO oidc_login_states (state_hash, code_verifier, nonce, expires_at)
+         VALUES (?, ?, ?, ?)"
+    )
+    .bind(hash_opaque_token(&login_state))
+    .bind(&code_verifier)
+    .bind(&nonce)
+    .bind((Utc::now() + Duration::minutes(LOGIN_STATE_EXPIRATION_MINUTES)).to_rfc3339())
+    .execute(&state.db_pool)
+    .await?;
+
+    let mut url = Url::parse(&metadata.authorization_endpoint)
+        .map_err(|e| AppError::InternalServerError(format!("authorization_endpoint inválido: {}", e)))?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", client.client_id)
+        .append_pair("redirect_uri", &client.redirect_url())
+        .append_pair("scope", &state.config.oidc_scopes)
+        .append_pair("state", &login_state)
+        .append_pair("nonce", &nonce)
+        .append_pair("code_challenge", &code_challenge)
+        .append_pair("code_challenge_method", "S256");
+
+    Ok((url.to_string(), login_state))
+}
+
+/// Completa el flujo: consume el `state`, canjea el código por tokens y valida el ID token
+/// (firma, emisor, audiencia, expiración y nonce).
+pub async fn complete_oidc_login(state: &AppState, code: &str, login_state: &str) -> Result<OidcIdentity> {
+    let client = OidcClient::from_config(&state.config)?;
+    let invalid = || AppError::Authentication("Petición de inicio de sesión inválida o expirada".to_string());
+
+    let row: LoginStateRow = sqlx::query_as(
+        "SELECT id, code_verifier, nonce, expires_at, used_at
+         FROM oidc_login_states WHERE state_hash = ?"
+    )
+    .bind(hash_opaque_token(login_state))
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(invalid)?;
+
+    let expires_at = DateTime::parse_from_rfc3339(&row.expires_at)
+        .map_err(|_| AppError::InternalServerError("Error parsing OIDC state expiration".to_string()))?;
+    if row.used_at.is_some() || Utc::now() >= expires_at {
+        return Err(invalid());
+    }
+
+    // El `state` es de un solo uso aunque el canje posterior falle.
+    let result = sqlx::query("UPDATE oidc_login_states SET used_at = ? WHERE id = ? AND used_at IS NULL")
+        .bind(Utc::now().to_rfc3339())
+        .bind(row.id)
+        .execute(&state.db_pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Err(invalid());
+    }
+
+    let metadata = client.metadata().await?;
+
+    let redirect_url = client.redirect_url();
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", redirect_url.as_str()),
+        ("client_id", client.client_id),
+        ("code_verifier", row.code_verifier.as_str()),
+    ];
+    if let Some(secret) = &state.config.oidc_client_secret {
+        form.push(("client_secret", secret.as_str()));
+    }
+
+    let response = client
+        .http
+        .post(&metadata.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .map_err(|e| AppError::InternalServerError(format!("Error contactando al proveedor OIDC: {}", e)))?;
+    if !response.status().is_success() {
+        println!("->> AUTH | El proveedor OIDC rechazó el código ({})", response.status());
+        return Err(AppError::Authentication("Código de autorización inválido".to_string()));
+    }
+    let tokens: TokenResponse = response
+        .json()
+        .await
+        .map_err(|e| AppError::InternalServerError(format!("Respuesta inválida del proveedor OIDC: {}", e)))?;
+
+    // Las claves que no se reconocen (p. ej. de cifrado) se ignoran en lugar de invalidar el JWKS.
+    let raw_jwks: serde_json::Value = client.get_json(&metadata.jwks_uri).await?;
+    let jwks = JwkSet {
+        keys: raw_jwks["keys"]
+            .as_array()
+            .map(|keys| keys.iter().filter_map(|k| serde_json::from_value(k.clone()).ok()).collect())
+            .unwrap_or_default(),
+    };
+    let claims = validate_id_token(&tokens.id_token, &jwks, &metadata.issuer, client.client_id)?;
+
+    if claims.get("nonce").and_then(|n| n.as_str()) != Some(row.nonce.as_str()) {
+        return Err(AppError::Authentication("Nonce del ID token inválido".to_string()));
+    }
+
+    let string_claim = |name: &str| claims.get(name).and_then(|v| v.as_str()).map(|v| v.to_string());
+    let groups = match claims.get(&state.config.oidc_groups_claim) {
+        Some(serde_json::Value::Array(values)) => values.iter().filter_map(|g| g.as_str().map(|g| g.to_string())).collect(),
+        Some(serde_json::Value::String(group)) => vec![group.clone()],
+        _ => Vec::new(),
+    };
+
+    Ok(OidcIdentity {
+        issuer: metadata.issuer,
+        subject: string_claim("sub").ok_or_else(|| AppError::Authentication("ID token sin `sub`".to_string()))?,
+        email: string_claim("email"),
+        email_verified: claims.get("email_verified").and_then(|v| v.as_bool()).unwrap_or(false),
+        name: string_claim("name"),
+        groups,
+    })
+}
+
+/// Devuelve el usuario local de una identidad OIDC: por vínculo existente, por email verificado
+/// o, si `OIDC_AUTO_PROVISION` está activo, creando la cuenta. Sincroniza el rol `admin`
+/// con el grupo `OIDC_ADMIN_GROUP` si está configurado; los roles personalizados no se tocan.
+pub async fn resolve_oidc_user(state: &AppState, identity: &OidcIdentity) -> Result<User> {
+    let now = Utc::now().to_rfc3339();
+
+    let linked_user: Option<i32> = sqlx::query_scalar(
+        "SELECT user_id FROM user_identities WHERE issuer = ? AND subject = ?"
+    )
+    .bind(&identity.issuer)
+    .bind(&identity.subject)
+    .fetch_optional(&state.db_pool)
+    .await?;
+
+    let user_id = match linked_user {
+        Some(user_id) => user_id,
+        None => {
+            let user_id = find_or_provision_user(state, identity).await?;
+            sqlx::query(
+                "INSERT INTO user_identities (user_id, issuer, subject, email) VALUES (?, ?, ?, ?)"
+            )
+            .bind(user_id)
+            .bind(&identity.issuer)
+            .bind(&identity.subject)
+            .bind(&identity.email)
+            .execute(&state.db_pool)
+            .await?;
+            println!("->> AUTH | Identidad OIDC vinculada al usuario (ID: {})", user_id);
+            user_id
+        }
+    };
+
+    sqlx::query("UPDATE user_identities SET last_login_at = ?, email = COALESCE(?, email) WHERE issuer = ? AND subject = ?")
+        .bind(&now)
+        .bind(&identity.email)
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .execute(&state.db_pool)
+        .await?;
+
+    if let Some(admin_group) = &state.config.oidc_admin_group {
+        let role = if identity.groups.iter().any(|g| g == admin_group) { "admin" } else { "user" };
+        let changed = sqlx::query("UPDATE users SET role = ? WHERE id = ? AND role != ? AND role IN ('admin', 'user')")
+            .bind(role)
+            .bind(user_id)
+            .bind(role)
+            .execute(&state.db_pool)
+            .await?
+            .rows_affected();
+        if changed > 0 {
+            bump_token_version(state, user_id).await?;
+        }
+    }
+
+    let user = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+    Ok(user)
+}
+
+async fn find_or_provision_user(state: &AppState, identity: &OidcIdentity) -> Result<i32> {
+    let no_account = || AppError::Authentication("No hay ninguna cuenta asociada a esta identidad".to_string());
+    let email = identity.email.as_deref().ok_or_else(no_account)?;
+
+    let existing: Option<i32> = sqlx::query_scalar("SELECT id FROM users WHERE email = ?")
+        .bind(email)
+        .fetch_optional(&state.db_pool)
+        .await?;
+
+    if let Some(user_id) = existing {
+        // Solo se vincula una cuenta existente si el proveedor garantiza el email.
+        if !identity.email_verified {
+            return Err(AppError::Conflict("El email ya está registrado".to_string()));
+        }
+        return Ok(user_id);
+    }
+
+    // Con el registro solo por invitación (activable en tiempo de ejecución) tampoco se crean
+    // cuentas desde el proveedor: bastaría con autenticarse en él para saltarse la invitación.
+    if !state.config.oidc_auto_provision || is_invite_only_registration(state).await? {
+        return Err(no_account());
+    }
+
+    // Las cuentas SSO no tienen contraseña local: se guarda el hash de un valor aleatorio.
+    let password_hash = hash_password(&state.config, &generate_opaque_token())?;
+    let name = identity
+        .name
+        .clone()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string());
+    let email_verified_at = identity.email_verified.then(|| Utc::now().to_rfc3339());
+
+    let user_id = sqlx::query(
+        "INSERT INTO users (name, email, password_hash, role, email_verified_at) VALUES (?, ?, ?, 'user', ?)"
+    )
+    .bind(&name)
+    .bind(email)
+    .bind(&password_hash)
+    .bind(&email_verified_at)
+    .execute(&state.db_pool)
+    .await?
+    .last_insert_rowid();
+
+    println!("->> AUTH | Usuario creado desde OIDC: {} (ID: {})", email, user_id);
+    Ok(user_id as i32)
+}
+
+fn validate_id_token(
+    id_token: &str,
+    jwks: &JwkSet,
+    issuer: &str,
+    client_id: &str,
+) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let header = decode_header(id_token)?;
+    if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+        return Err(AppError::Authentication("Algoritmo del ID token no permitido".to_string()));
+    }
+
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None => jwks.keys.first(),
+    }
+    .ok_or_else(|| AppError::Authentication("Clave de firma del ID token desconocida".to_string()))?;
+    let decoding_key = DecodingKey::from_jwk(jwk)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.set_audience(&[client_id]);
+
+    Ok(decode::<serde_json::Map<String, serde_json::Value>>(id_token, &decoding_key, &validation)?.claims)
+}
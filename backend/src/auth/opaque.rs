@@ -0,0 +1,16 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Genera un token opaco aleatorio (32 bytes codificados en hexadecimal).
+/// Se usa para refresh tokens y cualquier otro secreto que se entregue al cliente.
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Calcula el hash SHA-256 de un token opaco.
+/// En la base de datos solo se guarda este hash, nunca el token en claro.
+pub fn hash_opaque_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    auth::opaque::{generate_opaque_token, hash_opaque_token},
+    error::{AppError, Result},
+    AppState,
+};
+
+#[derive(sqlx::FromRow, Debug)]
+struct PasswordResetRow {
+    id: i64,
+    user_id: i32,
+    expires_at: String,
+    used_at: Option<String>,
+}
+
+/// Emite un token de restablecimiento de contraseña de un solo uso.
+/// Los tokens pendientes anteriores del usuario quedan invalidados.
+pub async fn issue_password_reset_token(state: &AppState, user_id: i32) -> Result<String> {
+    let token = generate_opaque_token();
+    let now = Utc::now();
+    let expires_at = now + Duration::minutes(state.config.password_reset_expiration_minutes);
+
+    let mut tx = state.db_pool.begin().await?;
+
+    sqlx::query("UPDATE password_reset_tokens SET used_at = ? WHERE user_id = ? AND used_at IS NULL")
+        .bind(now.to_rfc3339())
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at)
+         VALUES (?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(hash_opaque_token(&token))
+    .bind(expires_at.to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(token)
+}
+
+/// Consume un token de restablecimiento y devuelve el ID del usuario al que pertenece.
+pub async fn consume_password_reset_token(state: &AppState, token: &str) -> Result<i32> {
+    let invalid = || AppError::BadRequest("Token de restablecimiento inválido o expirado".to_string());
+
+    let row: PasswordResetRow = sqlx::query_as(
+        "SELECT id, user_id, expires_at, used_at
+         FROM password_reset_tokens WHERE token_hash = ?"
+    )
+    .bind(hash_opaque_token(token))
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(invalid)?;
+
+    if row.used_at.is_some() {
+        return Err(invalid());
+    }
+
+    let expires_at = DateTime::parse_from_rfc3339(&row.expires_at)
+        .map_err(|_| AppError::InternalServerError("Error parsing reset token expiration".to_string()))?;
+    if Utc::now() >= expires_at {
+        return Err(invalid());
+    }
+
+    // El `used_at IS NULL` garantiza que el token solo se pueda usar una vez.
+    let result = sqlx::query("UPDATE password_reset_tokens SET used_at = ? WHERE id = ? AND used_at IS NULL")
+        .bind(Utc::now().to_rfc3339())
+        .bind(row.id)
+        .execute(&state.db_pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(invalid());
+    }
+
+    Ok(row.user_id)
+}
@@ -0,0 +1,167 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2, Params, Version,
+};
+use rand::RngCore;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::{
+    config::Config,
+    error::{AppError, Result},
+};
+
+/// Contraseñas más comunes, rechazadas siempre que `PASSWORD_CHECK_COMMON` esté activo.
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+/// Algoritmo con el que se guardan las contraseñas nuevas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PasswordHashAlgorithm {
+    Bcrypt,
+    Argon2id,
+}
+
+impl PasswordHashAlgorithm {
+    pub fn from_string(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "bcrypt" => Some(PasswordHashAlgorithm::Bcrypt),
+            "argon2id" | "argon2" => Some(PasswordHashAlgorithm::Argon2id),
+            _ => None,
+        }
+    }
+}
+
+/// Genera el hash de una contraseña con el algoritmo y los parámetros configurados.
+pub fn hash_password(config: &Config, password: &str) -> Result<String> {
+    match config.password_hash_algorithm {
+        PasswordHashAlgorithm::Bcrypt => Ok(bcrypt::hash(password, config.bcrypt_cost)?),
+        PasswordHashAlgorithm::Argon2id => {
+            let mut salt = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut salt);
+            let salt = SaltString::encode_b64(&salt).map_err(argon2_error)?;
+
+            Ok(argon2_hasher(config)?
+                .hash_password(password.as_bytes(), &salt)
+                .map_err(argon2_error)?
+                .to_string())
+        }
+    }
+}
+
+/// Comprueba una contraseña contra un hash bcrypt o Argon2id, según el formato guardado.
+pub fn verify_password(password: &str, password_hash: &str) -> Result<bool> {
+    if password_hash.starts_with("$argon2") {
+        let parsed = PasswordHash::new(password_hash).map_err(argon2_error)?;
+        return Ok(Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok());
+    }
+
+    Ok(bcrypt::verify(password, password_hash)?)
+}
+
+/// Indica si un hash se generó con un algoritmo o parámetros distintos de los configurados
+/// (por ejemplo, un coste de bcrypt menor), en cuyo caso conviene regenerarlo en el próximo login.
+pub fn needs_rehash(config: &Config, password_hash: &str) -> bool {
+    match config.password_hash_algorithm {
+        PasswordHashAlgorithm::Bcrypt => {
+            let cost = password_hash.split('$').nth(2).and_then(|cost| cost.parse::<u32>().ok());
+            !password_hash.starts_with("$2") || cost.is_none_or(|cost| cost < config.bcrypt_cost)
+        }
+        PasswordHashAlgorithm::Argon2id => {
+            let Ok(parsed) = PasswordHash::new(password_hash) else {
+                return true;
+            };
+            let Ok(params) = Params::try_from(&parsed) else {
+                return true;
+            };
+            parsed.algorithm.as_str() != "argon2id"
+                || params.m_cost() != config.argon2_memory_kib
+                || params.t_cost() != config.argon2_iterations
+                || params.p_cost() != config.argon2_parallelism
+        }
+    }
+}
+
+/// Aplica la política de contraseñas: longitud mínima, clases de caracteres
+/// y lista de contraseñas comunes o filtradas. `field` es el campo de la petición.
+pub fn check_password_policy(config: &Config, field: &str, password: &str) -> Result<()> {
+    let invalid = |message: String| {
+        Err(AppError::Validation {
+            message: "La entrada proporcionada no es válida".to_string(),
+            fields: HashMap::from([(field.to_string(), message)]),
+        })
+    };
+
+    if password.chars().count() < config.password_min_length {
+        return invalid(format!("Password must be at least {} characters", config.password_min_length));
+    }
+
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .iter()
+    .filter(|present| **present)
+    .count();
+    if classes < config.password_min_character_classes {
+        return invalid(format!(
+            "Password must combine at least {} of: lowercase, uppercase, digits, symbols",
+            config.password_min_character_classes
+        ));
+    }
+
+    if config.password_check_common && is_common_password(config, password) {
+        return invalid("This password is too common or has appeared in a data breach".to_string());
+    }
+
+    Ok(())
+}
+
+fn is_common_password(config: &Config, password: &str) -> bool {
+    let password = password.to_lowercase();
+    if COMMON_PASSWORDS.lines().any(|common| common == password) {
+        return true;
+    }
+
+    config
+        .password_denylist_file
+        .as_deref()
+        .is_some_and(|path| load_denylist(path).contains(&password))
+}
+
+/// Carga (una sola vez) la lista local de contraseñas filtradas, una por línea.
+fn load_denylist(path: &str) -> &'static HashSet<String> {
+    static DENYLIST: OnceLock<HashSet<String>> = OnceLock::new();
+    DENYLIST.get_or_init(|| match std::fs::read_to_string(path) {
+        Ok(contents) => {
+            let list: HashSet<String> = contents
+                .lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty())
+                .collect();
+            println!("->> SECURITY | {} contraseñas filtradas cargadas desde {}", list.len(), path);
+            list
+        }
+        Err(err) => {
+            eprintln!("❌ No se pudo leer PASSWORD_DENYLIST_FILE ({}): {}", path, err);
+            HashSet::new()
+        }
+    })
+}
+
+fn argon2_hasher(config: &Config) -> Result<Argon2<'static>> {
+    let params = Params::new(
+        config.argon2_memory_kib,
+        config.argon2_iterations,
+        config.argon2_parallelism,
+        None,
+    )
+    .map_err(argon2_error)?;
+
+    Ok(Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params))
+}
+
+fn argon2_error(err: impl std::fmt::Display) -> AppError {
+    AppError::InternalServerError(format!("Error de Argon2: {}", err))
+}
@@ -0,0 +1,186 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    auth::opaque::{generate_opaque_token, hash_opaque_token},
+    error::{AppError, Result},
+    models::PersonalAccessToken,
+    AppState,
+};
+
+/// Prefijo de los tokens personales; permite distinguirlos de un JWT sin decodificarlos.
+pub const PERSONAL_TOKEN_PREFIX: &str = "tdp_";
+
+/// Permisos que puede conceder un token personal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenScope {
+    TasksRead,
+    TasksWrite,
+    /// Privilegios de administrador (solo si el usuario también es admin).
+    Admin,
+}
+
+impl TokenScope {
+    pub fn from_string(scope: &str) -> Option<Self> {
+        match scope.trim().to_lowercase().as_str() {
+            "tasks:read" => Some(TokenScope::TasksRead),
+            "tasks:write" => Some(TokenScope::TasksWrite),
+            "admin" => Some(TokenScope::Admin),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::TasksRead => "tasks:read",
+            TokenScope::TasksWrite => "tasks:write",
+            TokenScope::Admin => "admin",
+        }
+    }
+}
+
+/// Comprueba que las credenciales conceden `scope`. Sin lista de scopes
+/// (sesión con JWT) se concede todo.
+pub fn check_scope(scopes: &Option<Vec<TokenScope>>, scope: TokenScope) -> Result<()> {
+    match scopes {
+        Some(granted) if !granted.contains(&scope) => Err(AppError::Authentication(format!(
+            "El token no tiene el permiso '{}'",
+            scope.as_str()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Exige una sesión iniciada con contraseña: la gestión de la cuenta no se
+/// permite con tokens personales.
+pub fn check_session(scopes: &Option<Vec<TokenScope>>) -> Result<()> {
+    if scopes.is_some() {
+        return Err(AppError::Authentication(
+            "Esta operación requiere una sesión iniciada con usuario y contraseña".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(sqlx::FromRow, Debug)]
+struct PersonalTokenRow {
+    id: i32,
+    user_id: i32,
+    name: String,
+    token_prefix: String,
+    scopes: String,
+    expires_at: Option<String>,
+    last_used_at: Option<String>,
+    created_at: String,
+}
+
+impl PersonalTokenRow {
+    fn scopes(&self) -> Vec<TokenScope> {
+        self.scopes.split(',').filter_map(TokenScope::from_string).collect()
+    }
+
+    fn into_model(self) -> PersonalAccessToken {
+        PersonalAccessToken {
+            scopes: self.scopes().iter().map(|s| s.as_str().to_string()).collect(),
+            id: self.id,
+            name: self.name,
+            token_prefix: self.token_prefix,
+            expires_at: self.expires_at,
+            last_used_at: self.last_used_at,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Crea un token personal. El valor en claro solo se devuelve aquí.
+pub async fn create_personal_token(
+    state: &AppState,
+    user_id: i32,
+    name: &str,
+    scopes: &[TokenScope],
+    expires_in_days: Option<i64>,
+) -> Result<(String, PersonalAccessToken)> {
+    let token = format!("{}{}", PERSONAL_TOKEN_PREFIX, generate_opaque_token());
+    let token_prefix = token[..PERSONAL_TOKEN_PREFIX.len() + 8].to_string();
+    let expires_at = expires_in_days.map(|days| (Utc::now() + Duration::days(days)).to_rfc3339());
+    let scopes = scopes.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(",");
+
+    let id = sqlx::query(
+        "INSERT INTO personal_access_tokens (user_id, name, token_prefix, token_hash, scopes, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(&token_prefix)
+    .bind(hash_opaque_token(&token))
+    .bind(&scopes)
+    .bind(&expires_at)
+    .execute(&state.db_pool)
+    .await?
+    .last_insert_rowid();
+
+    let row: PersonalTokenRow = sqlx::query_as("SELECT * FROM personal_access_tokens WHERE id = ?")
+        .bind(id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    Ok((token, row.into_model()))
+}
+
+/// Lista los tokens personales del usuario, del más reciente al más antiguo.
+pub async fn list_personal_tokens(state: &AppState, user_id: i32) -> Result<Vec<PersonalAccessToken>> {
+    let rows: Vec<PersonalTokenRow> = sqlx::query_as(
+        "SELECT * FROM personal_access_tokens WHERE user_id = ? ORDER BY id DESC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(rows.into_iter().map(PersonalTokenRow::into_model).collect())
+}
+
+/// Elimina (revoca) un token personal del usuario.
+pub async fn delete_personal_token(state: &AppState, user_id: i32, token_id: i32) -> Result<()> {
+    let result = sqlx::query("DELETE FROM personal_access_tokens WHERE id = ? AND user_id = ?")
+        .bind(token_id)
+        .bind(user_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Token con ID {} no encontrado", token_id)));
+    }
+    Ok(())
+}
+
+/// Valida un token personal y devuelve `(token_id, user_id, scopes)`.
+/// Actualiza `last_used_at` como mucho una vez por minuto.
+pub async fn authenticate_personal_token(state: &AppState, token: &str) -> Result<(i32, i32, Vec<TokenScope>)> {
+    let invalid = || AppError::Authentication("Token personal inválido o expirado".to_string());
+
+    let row: PersonalTokenRow = sqlx::query_as("SELECT * FROM personal_access_tokens WHERE token_hash = ?")
+        .bind(hash_opaque_token(token))
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(invalid)?;
+
+    if let Some(expires_at) = &row.expires_at {
+        let expires_at = DateTime::parse_from_rfc3339(expires_at)
+            .map_err(|_| AppError::InternalServerError("Error parsing personal token expiration".to_string()))?;
+        if Utc::now() >= expires_at {
+            return Err(invalid());
+        }
+    }
+
+    let now = Utc::now();
+    sqlx::query(
+        "UPDATE personal_access_tokens SET last_used_at = ?
+         WHERE id = ? AND (last_used_at IS NULL OR datetime(last_used_at) <= datetime(?))"
+    )
+    .bind(now.to_rfc3339())
+    .bind(row.id)
+    .bind((now - Duration::minutes(1)).to_rfc3339())
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok((row.id, row.user_id, row.scopes()))
+}
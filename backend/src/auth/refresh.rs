@@ -0,0 +1,164 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    auth::opaque::{generate_opaque_token, hash_opaque_token},
+    error::{AppError, Result},
+    AppState,
+};
+
+#[derive(sqlx::FromRow, Debug)]
+struct RefreshTokenRow {
+    id: i64,
+    user_id: i32,
+    family_id: String,
+    expires_at: String,
+    revoked_at: Option<String>,
+}
+
+/// Emite un nuevo refresh token para el usuario y guarda su hash.
+/// Si no se indica una familia, se inicia una nueva (por ejemplo, en el login).
+pub async fn issue_refresh_token(
+    state: &AppState,
+    user_id: i32,
+    family_id: Option<&str>,
+) -> Result<String> {
+    let token = generate_opaque_token();
+    let family_id = family_id
+        .map(|f| f.to_string())
+        .unwrap_or_else(generate_opaque_token);
+    let expires_at = Utc::now() + Duration::days(state.config.refresh_token_expiration_days);
+
+    sqlx::query(
+        "INSERT INTO refresh_tokens (user_id, token_hash, family_id, expires_at)
+         VALUES (?, ?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(hash_opaque_token(&token))
+    .bind(&family_id)
+    .bind(expires_at.to_rfc3339())
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok(token)
+}
+
+/// Rota un refresh token: invalida el recibido y emite uno nuevo de la misma familia.
+/// Si el token ya había sido usado (reutilización), se revoca la familia completa.
+/// Devuelve el ID del usuario, la familia (ID de sesión) y el nuevo refresh token.
+pub async fn rotate_refresh_token(state: &AppState, token: &str) -> Result<(i32, String, String)> {
+    let row: RefreshTokenRow = sqlx::query_as(
+        "SELECT id, user_id, family_id, expires_at, revoked_at
+         FROM refresh_tokens WHERE token_hash = ?"
+    )
+    .bind(hash_opaque_token(token))
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::Authentication("Refresh token inválido".to_string()))?;
+
+    if row.revoked_at.is_some() {
+        println!("->> SECURITY | Reutilización de refresh token detectada (familia {}), revocando familia", row.family_id);
+        revoke_refresh_family(state, &row.family_id).await?;
+        return Err(AppError::Authentication("Refresh token inválido".to_string()));
+    }
+
+    let expires_at = DateTime::parse_from_rfc3339(&row.expires_at)
+        .map_err(|_| AppError::InternalServerError("Error parsing refresh token expiration".to_string()))?;
+    if Utc::now() >= expires_at {
+        return Err(AppError::Authentication("Refresh token expirado".to_string()));
+    }
+
+    let mut tx = state.db_pool.begin().await?;
+
+    // El `revoked_at IS NULL` evita que dos peticiones concurrentes roten el mismo token.
+    let result = sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL"
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(row.id)
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        tx.rollback().await?;
+        revoke_refresh_family(state, &row.family_id).await?;
+        return Err(AppError::Authentication("Refresh token inválido".to_string()));
+    }
+
+    let new_token = generate_opaque_token();
+    let new_expires_at = Utc::now() + Duration::days(state.config.refresh_token_expiration_days);
+
+    let new_id = sqlx::query(
+        "INSERT INTO refresh_tokens (user_id, token_hash, family_id, expires_at)
+         VALUES (?, ?, ?, ?)"
+    )
+    .bind(row.user_id)
+    .bind(hash_opaque_token(&new_token))
+    .bind(&row.family_id)
+    .bind(new_expires_at.to_rfc3339())
+    .execute(&mut *tx)
+    .await?
+    .last_insert_rowid();
+
+    sqlx::query("UPDATE refresh_tokens SET replaced_by = ? WHERE id = ?")
+        .bind(new_id)
+        .bind(row.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok((row.user_id, row.family_id, new_token))
+}
+
+/// Revoca todos los refresh tokens activos de una familia y cierra la sesión asociada.
+pub async fn revoke_refresh_family(state: &AppState, family_id: &str) -> Result<()> {
+    let now = Utc::now().to_rfc3339();
+
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = ? WHERE family_id = ? AND revoked_at IS NULL"
+    )
+    .bind(&now)
+    .bind(family_id)
+    .execute(&state.db_pool)
+    .await?;
+
+    sqlx::query("UPDATE sessions SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+        .bind(&now)
+        .bind(family_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    state.open_sessions.remove(&family_id.to_string());
+    Ok(())
+}
+
+/// Revoca la familia a la que pertenece un refresh token de `user_id` (por ejemplo, en el logout).
+/// Los tokens desconocidos o de otros usuarios se ignoran.
+pub async fn revoke_refresh_token(state: &AppState, user_id: i32, token: &str) -> Result<()> {
+    let family_id: Option<String> = sqlx::query_scalar(
+        "SELECT family_id FROM refresh_tokens WHERE token_hash = ? AND user_id = ?"
+    )
+    .bind(hash_opaque_token(token))
+    .bind(user_id)
+    .fetch_optional(&state.db_pool)
+    .await?;
+
+    if let Some(family_id) = family_id {
+        revoke_refresh_family(state, &family_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Revoca todos los refresh tokens activos de un usuario (por ejemplo, tras cambiar la contraseña).
+pub async fn revoke_user_refresh_tokens(state: &AppState, user_id: i32) -> Result<()> {
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL"
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(user_id)
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use chrono::{TimeZone, Utc};
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+use crate::{error::Result, AppState};
+
+/// Agrega el `jti` de un access token a la lista de tokens revocados.
+/// La entrada se conserva solo hasta la expiración natural del token.
+pub async fn revoke_jti(state: &AppState, jti: &str, expires_at: i64) -> Result<()> {
+    let expires_at = Utc
+        .timestamp_opt(expires_at, 0)
+        .single()
+        .unwrap_or_else(Utc::now);
+
+    sqlx::query("INSERT OR IGNORE INTO revoked_tokens (jti, expires_at) VALUES (?, ?)")
+        .bind(jti)
+        .bind(expires_at.to_rfc3339())
+        .execute(&state.db_pool)
+        .await?;
+
+    state.revoked_jtis.insert(jti.to_string(), true);
+    Ok(())
+}
+
+/// Indica si el `jti` de un access token fue revocado. Solo se consulta la base de datos si
+/// no está en caché.
+pub async fn is_jti_revoked(state: &AppState, jti: &str) -> Result<bool> {
+    if let Some(revoked) = state.revoked_jtis.get(&jti.to_string()) {
+        return Ok(revoked);
+    }
+
+    let revoked: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = ?)")
+        .bind(jti)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    state.revoked_jtis.insert(jti.to_string(), revoked);
+    Ok(revoked)
+}
+
+/// Elimina las revocaciones y refresh tokens que ya expiraron.
+/// Una vez expirado, un token es rechazado por sí mismo y no hace falta recordarlo.
+pub async fn prune_expired_tokens(db_pool: &SqlitePool) -> Result<u64> {
+    let now = Utc::now().to_rfc3339();
+
+    let revoked = sqlx::query("DELETE FROM revoked_tokens WHERE datetime(expires_at) <= datetime(?)")
+        .bind(&now)
+        .execute(db_pool)
+        .await?
+        .rows_affected();
+
+    let refresh = sqlx::query("DELETE FROM refresh_tokens WHERE datetime(expires_at) <= datetime(?)")
+        .bind(&now)
+        .execute(db_pool)
+        .await?
+        .rows_affected();
+
+    let challenges = sqlx::query("DELETE FROM two_factor_challenges WHERE datetime(expires_at) <= datetime(?)")
+        .bind(&now)
+        .execute(db_pool)
+        .await?
+        .rows_affected();
+
+    let oidc_states = sqlx::query("DELETE FROM oidc_login_states WHERE datetime(expires_at) <= datetime(?)")
+        .bind(&now)
+        .execute(db_pool)
+        .await?
+        .rows_affected();
+
+    // Las sesiones cerradas o sin refresh tokens vigentes ya no pueden usarse:
+    // al no existir, sus access tokens también se rechazan.
+    let sessions = sqlx::query(
+        "DELETE FROM sessions
+         WHERE revoked_at IS NOT NULL
+            OR (datetime(last_active_at) <= datetime(?) AND NOT EXISTS (
+                SELECT 1 FROM refresh_tokens r
+                WHERE r.family_id = sessions.id AND r.revoked_at IS NULL
+            ))"
+    )
+    .bind((Utc::now() - chrono::Duration::minutes(5)).to_rfc3339())
+    .execute(db_pool)
+    .await?
+    .rows_affected();
+
+    Ok(revoked + refresh + challenges + oidc_states + sessions)
+}
+
+/// Lanza una tarea en segundo plano que purga periódicamente los tokens expirados.
+pub fn spawn_token_pruner(db_pool: SqlitePool, interval_minutes: u64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_minutes.max(1) * 60));
+        loop {
+            interval.tick().await;
+            match prune_expired_tokens(&db_pool).await {
+                Ok(0) => {}
+                Ok(pruned) => println!("->> SECURITY | {} tokens expirados purgados", pruned),
+                Err(err) => eprintln!("❌ Error purgando tokens expirados: {}", err),
+            }
+        }
+    });
+}
@@ -0,0 +1,182 @@
+use chrono::{Duration, Utc};
+
+use crate::{
+    auth::opaque::generate_opaque_token,
+    auth::refresh::revoke_refresh_family,
+    config::Config,
+    error::{AppError, Result},
+    AppState,
+};
+
+/// Intervalo mínimo entre actualizaciones de `last_active_at`.
+const ACTIVITY_UPDATE_INTERVAL_SECONDS: i64 = 60;
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct SessionRow {
+    pub id: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+    pub last_active_at: String,
+}
+
+/// Abre una sesión para el dispositivo que acaba de iniciar sesión y devuelve su ID.
+pub async fn create_session(
+    state: &AppState,
+    user_id: i32,
+    ip_address: &str,
+    user_agent: Option<&str>,
+) -> Result<String> {
+    let session_id = generate_opaque_token();
+    let now = Utc::now().to_rfc3339();
+
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, ip_address, user_agent, created_at, last_active_at)
+         VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&session_id)
+    .bind(user_id)
+    .bind(ip_address)
+    .bind(user_agent)
+    .bind(&now)
+    .bind(&now)
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok(session_id)
+}
+
+/// Caducidad de la caché de sesiones abiertas: no más que el intervalo de actualización de la
+/// actividad, para que `last_active_at` siga registrándose con la misma frecuencia.
+pub fn open_sessions_ttl(config: &Config) -> u64 {
+    config.auth_cache_ttl_seconds.min(ACTIVITY_UPDATE_INTERVAL_SECONDS as u64)
+}
+
+/// Comprueba que la sesión sigue abierta y registra la actividad (como mucho una vez por minuto).
+/// Las sesiones cerradas o purgadas se rechazan. Mientras la sesión está en caché no se consulta
+/// la base de datos.
+pub async fn touch_session(state: &AppState, session_id: &str, ip_address: Option<&str>) -> Result<()> {
+    if state.open_sessions.get(&session_id.to_string()).is_some() {
+        return Ok(());
+    }
+
+    let revoked_at: Option<Option<String>> = sqlx::query_scalar("SELECT revoked_at FROM sessions WHERE id = ?")
+        .bind(session_id)
+        .fetch_optional(&state.db_pool)
+        .await?;
+
+    match revoked_at {
+        Some(None) => {}
+        _ => return Err(AppError::Authentication("Sesión cerrada".to_string())),
+    }
+
+    let now = Utc::now();
+    sqlx::query(
+        "UPDATE sessions SET last_active_at = ?, ip_address = COALESCE(?, ip_address)
+         WHERE id = ? AND datetime(last_active_at) <= datetime(?)"
+    )
+    .bind(now.to_rfc3339())
+    .bind(ip_address)
+    .bind(session_id)
+    .bind((now - Duration::seconds(ACTIVITY_UPDATE_INTERVAL_SECONDS)).to_rfc3339())
+    .execute(&state.db_pool)
+    .await?;
+
+    state.open_sessions.insert(session_id.to_string(), ());
+    Ok(())
+}
+
+/// Sesiones abiertas del usuario, de la más reciente a la más antigua.
+pub async fn list_sessions(state: &AppState, user_id: i32) -> Result<Vec<SessionRow>> {
+    let sessions = sqlx::query_as(
+        "SELECT id, ip_address, user_agent, created_at, last_active_at
+         FROM sessions
+         WHERE user_id = ? AND revoked_at IS NULL
+         ORDER BY datetime(last_active_at) DESC"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    Ok(sessions)
+}
+
+/// Cierra una sesión del usuario: sus access tokens dejan de aceptarse y
+/// sus refresh tokens quedan revocados.
+pub async fn revoke_session(state: &AppState, user_id: i32, session_id: &str) -> Result<()> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = ? WHERE id = ? AND user_id = ? AND revoked_at IS NULL"
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(session_id)
+    .bind(user_id)
+    .execute(&state.db_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Sesión no encontrada".to_string()));
+    }
+
+    revoke_refresh_family(state, session_id).await
+}
+
+/// Cierra todas las sesiones del usuario salvo `keep` (si se indica).
+/// Devuelve el número de sesiones cerradas.
+pub async fn revoke_user_sessions(state: &AppState, user_id: i32, keep: Option<&str>) -> Result<u64> {
+    let session_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM sessions WHERE user_id = ? AND revoked_at IS NULL"
+    )
+    .bind(user_id)
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let mut revoked = 0;
+    for session_id in session_ids.iter().filter(|id| Some(id.as_str()) != keep) {
+        revoke_session(state, user_id, session_id).await?;
+        revoked += 1;
+    }
+
+    Ok(revoked)
+}
+
+/// Descripción legible del dispositivo a partir del user agent, p. ej. "Firefox en Linux".
+pub fn describe_device(user_agent: Option<&str>) -> String {
+    let Some(ua) = user_agent.filter(|ua| !ua.trim().is_empty()) else {
+        return "Dispositivo desconocido".to_string();
+    };
+
+    let client = if ua.contains("Edg/") {
+        "Edge"
+    } else if ua.contains("OPR/") || ua.contains("Opera") {
+        "Opera"
+    } else if ua.contains("Firefox/") {
+        "Firefox"
+    } else if ua.contains("Chrome/") || ua.contains("CriOS/") {
+        "Chrome"
+    } else if ua.contains("Safari/") {
+        "Safari"
+    } else if ua.starts_with("curl/") {
+        "curl"
+    } else {
+        return ua.chars().take(60).collect();
+    };
+
+    let os = if ua.contains("Windows") {
+        Some("Windows")
+    } else if ua.contains("iPhone") || ua.contains("iPad") {
+        Some("iOS")
+    } else if ua.contains("Mac OS X") || ua.contains("Macintosh") {
+        Some("macOS")
+    } else if ua.contains("Android") {
+        Some("Android")
+    } else if ua.contains("Linux") {
+        Some("Linux")
+    } else {
+        None
+    };
+
+    match os {
+        Some(os) => format!("{} en {}", client, os),
+        None => client.to_string(),
+    }
+}
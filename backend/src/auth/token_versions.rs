@@ -0,0 +1,34 @@
+use crate::{
+    error::{AppError, Result},
+    AppState,
+};
+
+/// Versión de token vigente de un usuario. Los JWT llevan la versión con la que se emitieron;
+/// si ya no coincide, el token es anterior a un cambio de rol, de estado o a la eliminación
+/// de la cuenta y se rechaza. Solo se consulta la base de datos si no está en caché.
+pub async fn current_token_version(state: &AppState, user_id: i32) -> Result<i64> {
+    if let Some(version) = state.token_versions.get(&user_id) {
+        return Ok(version);
+    }
+
+    let version: i64 = sqlx::query_scalar("SELECT token_version FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::Authentication("Usuario no encontrado en la base de datos".to_string()))?;
+
+    state.token_versions.insert(user_id, version);
+    Ok(version)
+}
+
+/// Invalida todos los JWT emitidos hasta ahora para el usuario. Los refresh tokens siguen
+/// sirviendo para obtener uno nuevo con el rol actualizado, si la cuenta sigue activa.
+pub async fn bump_token_version(state: &AppState, user_id: i32) -> Result<()> {
+    sqlx::query("UPDATE users SET token_version = token_version + 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    state.token_versions.remove(&user_id);
+    Ok(())
+}
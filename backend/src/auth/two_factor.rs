@@ -0,0 +1,306 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{
+    auth::opaque::{generate_opaque_token, hash_opaque_token},
+    error::{AppError, Result},
+    AppState,
+};
+
+/// Emisor mostrado en las apps de autenticación.
+const TOTP_ISSUER: &str = "To-Do API";
+/// Duración de cada código TOTP, en segundos.
+const TOTP_STEP_SECONDS: u64 = 30;
+/// Pasos de tolerancia a cada lado para compensar desfases de reloj.
+const TOTP_SKEW_STEPS: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const CHALLENGE_EXPIRATION_MINUTES: i64 = 5;
+const CHALLENGE_MAX_ATTEMPTS: i64 = 5;
+
+#[derive(sqlx::FromRow, Debug)]
+struct TwoFactorUserRow {
+    email: String,
+    totp_secret: Option<String>,
+    totp_enabled_at: Option<String>,
+    totp_last_step: Option<i64>,
+}
+
+#[derive(sqlx::FromRow, Debug)]
+struct ChallengeRow {
+    id: i64,
+    user_id: i32,
+    attempts: i64,
+    expires_at: String,
+    used_at: Option<String>,
+}
+
+/// Inicia el alta de 2FA: genera un secreto nuevo (aún inactivo) y devuelve
+/// el secreto en base32 junto con la URL `otpauth://` para el código QR.
+pub async fn begin_totp_enrollment(state: &AppState, user_id: i32) -> Result<(String, String)> {
+    let user = load_two_factor_user(state, user_id).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err(AppError::Conflict("La autenticación en dos pasos ya está activada".to_string()));
+    }
+
+    let mut secret_bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut secret_bytes);
+    let secret = Secret::Raw(secret_bytes.to_vec()).to_encoded().to_string();
+    let totp = build_totp(&secret, &user.email)?;
+
+    sqlx::query("UPDATE users SET totp_secret = ?, totp_last_step = NULL WHERE id = ?")
+        .bind(&secret)
+        .bind(user_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    Ok((secret, totp.get_url()))
+}
+
+/// Confirma el alta con un código de la app y activa 2FA.
+/// Devuelve los códigos de recuperación, que solo se muestran esta vez.
+pub async fn confirm_totp_enrollment(state: &AppState, user_id: i32, code: &str) -> Result<Vec<String>> {
+    let user = load_two_factor_user(state, user_id).await?;
+    if user.totp_enabled_at.is_some() {
+        return Err(AppError::Conflict("La autenticación en dos pasos ya está activada".to_string()));
+    }
+    if user.totp_secret.is_none() {
+        return Err(AppError::BadRequest("Primero inicia la configuración de la autenticación en dos pasos".to_string()));
+    }
+
+    if !verify_totp_code(state, user_id, &user, code).await? {
+        return Err(AppError::BadRequest("Código de verificación inválido".to_string()));
+    }
+
+    sqlx::query("UPDATE users SET totp_enabled_at = ? WHERE id = ?")
+        .bind(Utc::now().to_rfc3339())
+        .bind(user_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    regenerate_recovery_codes(state, user_id).await
+}
+
+/// Desactiva 2FA y elimina el secreto y los códigos de recuperación.
+pub async fn disable_two_factor(state: &AppState, user_id: i32) -> Result<()> {
+    let mut tx = state.db_pool.begin().await?;
+
+    sqlx::query("UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL WHERE id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM two_factor_challenges WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sustituye los códigos de recuperación del usuario por un juego nuevo.
+pub async fn regenerate_recovery_codes(state: &AppState, user_id: i32) -> Result<Vec<String>> {
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let raw = &generate_opaque_token()[..10];
+            format!("{}-{}", &raw[..5], &raw[5..])
+        })
+        .collect();
+
+    let mut tx = state.db_pool.begin().await?;
+
+    sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for code in &codes {
+        sqlx::query("INSERT INTO two_factor_recovery_codes (user_id, code_hash) VALUES (?, ?)")
+            .bind(user_id)
+            .bind(hash_opaque_token(&normalize_recovery_code(code)))
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(codes)
+}
+
+/// Comprueba un segundo factor: un código TOTP de 6 dígitos o un código de recuperación.
+/// Un código de recuperación válido queda consumido.
+pub async fn verify_second_factor(state: &AppState, user_id: i32, code: &str) -> Result<bool> {
+    let user = load_two_factor_user(state, user_id).await?;
+    if user.totp_enabled_at.is_none() {
+        return Ok(false);
+    }
+
+    let code = code.trim();
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        return verify_totp_code(state, user_id, &user, code).await;
+    }
+
+    let result = sqlx::query(
+        "UPDATE two_factor_recovery_codes SET used_at = ?
+         WHERE user_id = ? AND code_hash = ? AND used_at IS NULL"
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(user_id)
+    .bind(hash_opaque_token(&normalize_recovery_code(code)))
+    .execute(&state.db_pool)
+    .await?;
+
+    if result.rows_affected() > 0 {
+        println!("->> AUTH | Código de recuperación usado (ID: {})", user_id);
+    }
+    Ok(result.rows_affected() > 0)
+}
+
+/// Emite el token del segundo paso del login, tras validar la contraseña.
+pub async fn issue_two_factor_challenge(state: &AppState, user_id: i32) -> Result<(String, i64)> {
+    let token = generate_opaque_token();
+    let expires_at = Utc::now() + Duration::minutes(CHALLENGE_EXPIRATION_MINUTES);
+
+    sqlx::query(
+        "INSERT INTO two_factor_challenges (user_id, token_hash, expires_at)
+         VALUES (?, ?, ?)"
+    )
+    .bind(user_id)
+    .bind(hash_opaque_token(&token))
+    .bind(expires_at.to_rfc3339())
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok((token, CHALLENGE_EXPIRATION_MINUTES * 60))
+}
+
+/// Email de la cuenta a la que pertenece un token del segundo paso, aunque ya no sea válido.
+/// Permite contar los códigos erróneos para el bloqueo de la cuenta.
+pub async fn two_factor_challenge_email(state: &AppState, token: &str) -> Result<Option<String>> {
+    let email = sqlx::query_scalar(
+        "SELECT u.email FROM two_factor_challenges c JOIN users u ON u.id = c.user_id
+         WHERE c.token_hash = ?"
+    )
+    .bind(hash_opaque_token(token))
+    .fetch_optional(&state.db_pool)
+    .await?;
+
+    Ok(email)
+}
+
+/// Canjea el token del segundo paso con un código TOTP o de recuperación.
+/// Devuelve el ID del usuario; tras varios intentos fallidos el token deja de ser válido.
+pub async fn complete_two_factor_challenge(state: &AppState, token: &str, code: &str) -> Result<i32> {
+    let invalid = || AppError::Authentication("Verificación en dos pasos inválida o expirada".to_string());
+
+    let row: ChallengeRow = sqlx::query_as(
+        "SELECT id, user_id, attempts, expires_at, used_at
+         FROM two_factor_challenges WHERE token_hash = ?"
+    )
+    .bind(hash_opaque_token(token))
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(invalid)?;
+
+    if row.used_at.is_some() || row.attempts >= CHALLENGE_MAX_ATTEMPTS {
+        return Err(invalid());
+    }
+
+    let expires_at = DateTime::parse_from_rfc3339(&row.expires_at)
+        .map_err(|_| AppError::InternalServerError("Error parsing two-factor challenge expiration".to_string()))?;
+    if Utc::now() >= expires_at {
+        return Err(invalid());
+    }
+
+    if !verify_second_factor(state, row.user_id, code).await? {
+        sqlx::query("UPDATE two_factor_challenges SET attempts = attempts + 1 WHERE id = ?")
+            .bind(row.id)
+            .execute(&state.db_pool)
+            .await?;
+        return Err(AppError::Authentication("Código de verificación inválido".to_string()));
+    }
+
+    let result = sqlx::query("UPDATE two_factor_challenges SET used_at = ? WHERE id = ? AND used_at IS NULL")
+        .bind(Utc::now().to_rfc3339())
+        .bind(row.id)
+        .execute(&state.db_pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(invalid());
+    }
+
+    Ok(row.user_id)
+}
+
+async fn load_two_factor_user(state: &AppState, user_id: i32) -> Result<TwoFactorUserRow> {
+    sqlx::query_as("SELECT email, totp_secret, totp_enabled_at, totp_last_step FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Usuario no encontrado".to_string()))
+}
+
+/// Valida un código TOTP dentro de la ventana de tolerancia y registra el paso usado,
+/// de modo que el mismo código (o uno anterior) no se pueda volver a aceptar.
+async fn verify_totp_code(state: &AppState, user_id: i32, user: &TwoFactorUserRow, code: &str) -> Result<bool> {
+    let Some(secret) = &user.totp_secret else {
+        return Ok(false);
+    };
+    let totp = build_totp(secret, &user.email)?;
+
+    let now = Utc::now().timestamp();
+    let matched_step = (-TOTP_SKEW_STEPS..=TOTP_SKEW_STEPS)
+        .map(|skew| now / TOTP_STEP_SECONDS as i64 + skew)
+        .filter(|step| *step >= 0)
+        .find(|step| totp.generate(*step as u64 * TOTP_STEP_SECONDS) == code.trim());
+
+    let Some(step) = matched_step else {
+        return Ok(false);
+    };
+    if user.totp_last_step.is_some_and(|last| step <= last) {
+        return Ok(false);
+    }
+
+    let result = sqlx::query(
+        "UPDATE users SET totp_last_step = ?
+         WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)"
+    )
+    .bind(step)
+    .bind(user_id)
+    .bind(step)
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+fn build_totp(secret: &str, account_name: &str) -> Result<TOTP> {
+    let secret_bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| AppError::InternalServerError(format!("Secreto TOTP inválido: {:?}", e)))?;
+
+    // Se usa `skew = 0` porque la tolerancia se aplica en `verify_totp_code`.
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        TOTP_STEP_SECONDS,
+        secret_bytes,
+        Some(TOTP_ISSUER.to_string()),
+        account_name.to_string(),
+    )
+    .map_err(|e| AppError::InternalServerError(format!("Error configurando TOTP: {}", e)))
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+}
@@ -1,26 +1,139 @@
 use std::env;
 
+use crate::auth::{keys::SigningAlgorithm, passwords::PasswordHashAlgorithm};
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub database_url: String,
     pub jwt_secret: String,
+    pub jwt_algorithm: SigningAlgorithm,
+    pub jwt_key_rotation_hours: i64,
     pub jwt_expiration_hours: i64,
+    pub refresh_token_expiration_days: i64,
+    pub token_prune_interval_minutes: u64,
     pub port: u16,
     pub host: String,
     pub allow_past_due_dates: bool,
+    /// URL pública del frontend, usada para construir los enlaces de los correos.
+    pub app_base_url: String,
+    /// Transporte de correo: `outbox` (log/archivos) o `smtp`.
+    pub mail_transport: String,
+    pub mail_from: String,
+    pub mail_outbox_dir: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub password_reset_expiration_minutes: i64,
+    /// Si está activo, `login_user` rechaza las cuentas con email sin verificar.
+    pub require_email_verification: bool,
+    pub email_verification_expiration_hours: i64,
+    /// Si está activo, las cuentas con rol `admin` deben tener 2FA para usar la API.
+    pub require_admin_2fa: bool,
+    /// Proveedor OpenID Connect para el inicio de sesión único (desactivado si no se indica).
+    pub oidc_issuer_url: Option<String>,
+    pub oidc_client_id: Option<String>,
+    pub oidc_client_secret: Option<String>,
+    /// URL del frontend a la que vuelve el proveedor (por defecto `{APP_BASE_URL}/auth/oidc/callback`).
+    pub oidc_redirect_url: Option<String>,
+    pub oidc_scopes: String,
+    /// Crea la cuenta en el primer login si no existe ningún usuario con ese email.
+    pub oidc_auto_provision: bool,
+    pub oidc_groups_claim: String,
+    /// Grupo del proveedor que otorga el rol `admin` (sin valor, los roles no se sincronizan).
+    pub oidc_admin_group: Option<String>,
+    pub password_min_length: usize,
+    /// Cuántas clases distintas (minúsculas, mayúsculas, dígitos, símbolos) debe combinar una contraseña.
+    pub password_min_character_classes: usize,
+    /// Rechaza las contraseñas de la lista integrada de contraseñas comunes.
+    pub password_check_common: bool,
+    /// Archivo local con contraseñas filtradas (una por línea), además de la lista integrada.
+    pub password_denylist_file: Option<String>,
+    /// Algoritmo para los hashes nuevos; los existentes se regeneran al iniciar sesión.
+    pub password_hash_algorithm: PasswordHashAlgorithm,
+    pub bcrypt_cost: u32,
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    /// Intentos fallidos seguidos (por email) a partir de los cuales se bloquea la cuenta.
+    pub login_lockout_threshold: i64,
+    /// Duración del primer bloqueo; cada fallo adicional la duplica hasta `login_lockout_max_minutes`.
+    pub login_lockout_base_minutes: i64,
+    pub login_lockout_max_minutes: i64,
+    /// Cierra el registro público: `/auth/register` exige una invitación (modificable en tiempo de ejecución).
+    pub invite_only_registration: bool,
+    pub invite_expiration_days: i64,
+    /// Duración de los tokens de suplantación emitidos por `/admin/users/{id}/impersonate`.
+    pub impersonation_token_minutes: i64,
+    /// Permite que los frontends pidan la sesión en cookies HttpOnly en lugar de en el cuerpo.
+    pub auth_cookies_enabled: bool,
+    pub auth_cookie_secure: bool,
+    /// Valor de `SameSite` para las cookies de sesión: `Strict`, `Lax` o `None`.
+    pub auth_cookie_same_site: String,
+    pub auth_cookie_domain: Option<String>,
+    /// Segundos que se guardan en memoria las versiones de token y los permisos de los roles.
+    /// Acota el retraso con que una instancia ve los cambios hechos por otra.
+    pub auth_cache_ttl_seconds: u64,
+    /// Impide marcar como `done` una tarea mientras alguna de las que la bloquean siga abierta.
+    pub block_done_with_open_dependencies: bool,
 }
 
 impl Config {
     pub fn from_env() -> Result<Self, String> {
+        let jwt_algorithm = SigningAlgorithm::from_string(
+            &env::var("JWT_ALGORITHM").unwrap_or_else(|_| "HS256".to_string()),
+        )
+        .ok_or_else(|| "JWT_ALGORITHM must be HS256, RS256 or EdDSA".to_string())?;
+
+        let password_hash_algorithm = PasswordHashAlgorithm::from_string(
+            &env::var("PASSWORD_HASH_ALGORITHM").unwrap_or_else(|_| "bcrypt".to_string()),
+        )
+        .ok_or_else(|| "PASSWORD_HASH_ALGORITHM must be bcrypt or argon2id".to_string())?;
+
+        let auth_cookie_same_site = match env::var("AUTH_COOKIE_SAME_SITE")
+            .unwrap_or_else(|_| "Strict".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "strict" => "Strict",
+            "lax" => "Lax",
+            "none" => "None",
+            _ => return Err("AUTH_COOKIE_SAME_SITE must be Strict, Lax or None".to_string()),
+        };
+
+        let mail_transport = env::var("MAIL_TRANSPORT")
+            .unwrap_or_else(|_| "outbox".to_string())
+            .to_lowercase();
+        if !matches!(mail_transport.as_str(), "smtp" | "outbox") {
+            return Err("MAIL_TRANSPORT must be smtp or outbox".to_string());
+        }
+
         Ok(Config {
             database_url: env::var("DATABASE_URL")
                 .map_err(|_| "DATABASE_URL must be set".to_string())?,
-            jwt_secret: env::var("JWT_SECRET")
-                .map_err(|_| "JWT_SECRET must be set".to_string())?,
+            // El secreto compartido solo es obligatorio con HS256.
+            jwt_secret: match env::var("JWT_SECRET") {
+                Ok(secret) => secret,
+                Err(_) if jwt_algorithm.is_asymmetric() => String::new(),
+                Err(_) => return Err("JWT_SECRET must be set".to_string()),
+            },
+            jwt_algorithm,
+            jwt_key_rotation_hours: env::var("JWT_KEY_ROTATION_HOURS")
+                .unwrap_or_else(|_| "720".to_string())
+                .parse()
+                .map_err(|_| "JWT_KEY_ROTATION_HOURS must be a valid number".to_string())?,
             jwt_expiration_hours: env::var("JWT_EXPIRATION_HOURS")
                 .unwrap_or_else(|_| "24".to_string())
                 .parse()
                 .map_err(|_| "JWT_EXPIRATION_HOURS must be a valid number".to_string())?,
+            refresh_token_expiration_days: env::var("REFRESH_TOKEN_EXPIRATION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .map_err(|_| "REFRESH_TOKEN_EXPIRATION_DAYS must be a valid number".to_string())?,
+            token_prune_interval_minutes: env::var("TOKEN_PRUNE_INTERVAL_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .map_err(|_| "TOKEN_PRUNE_INTERVAL_MINUTES must be a valid number".to_string())?,
             port: env::var("PORT")
                 .unwrap_or_else(|_| "3000".to_string())
                 .parse()
@@ -31,6 +144,120 @@ impl Config {
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()
                 .map_err(|_| "ALLOW_PAST_DUE_DATES must be true or false".to_string())?,
+            app_base_url: env::var("APP_BASE_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:3001".to_string()),
+            mail_transport,
+            mail_from: env::var("MAIL_FROM")
+                .unwrap_or_else(|_| "To-Do API <no-reply@todoapi.local>".to_string()),
+            mail_outbox_dir: env::var("MAIL_OUTBOX_DIR").ok(),
+            smtp_host: env::var("SMTP_HOST").ok(),
+            smtp_port: env::var("SMTP_PORT")
+                .unwrap_or_else(|_| "587".to_string())
+                .parse()
+                .map_err(|_| "SMTP_PORT must be a valid number".to_string())?,
+            smtp_username: env::var("SMTP_USERNAME").ok(),
+            smtp_password: env::var("SMTP_PASSWORD").ok(),
+            password_reset_expiration_minutes: env::var("PASSWORD_RESET_EXPIRATION_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .map_err(|_| "PASSWORD_RESET_EXPIRATION_MINUTES must be a valid number".to_string())?,
+            require_email_verification: env::var("REQUIRE_EMAIL_VERIFICATION")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| "REQUIRE_EMAIL_VERIFICATION must be true or false".to_string())?,
+            email_verification_expiration_hours: env::var("EMAIL_VERIFICATION_EXPIRATION_HOURS")
+                .unwrap_or_else(|_| "48".to_string())
+                .parse()
+                .map_err(|_| "EMAIL_VERIFICATION_EXPIRATION_HOURS must be a valid number".to_string())?,
+            require_admin_2fa: env::var("REQUIRE_ADMIN_2FA")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| "REQUIRE_ADMIN_2FA must be true or false".to_string())?,
+            oidc_issuer_url: env::var("OIDC_ISSUER_URL").ok(),
+            oidc_client_id: env::var("OIDC_CLIENT_ID").ok(),
+            oidc_client_secret: env::var("OIDC_CLIENT_SECRET").ok(),
+            oidc_redirect_url: env::var("OIDC_REDIRECT_URL").ok(),
+            oidc_scopes: env::var("OIDC_SCOPES")
+                .unwrap_or_else(|_| "openid email profile".to_string()),
+            oidc_auto_provision: env::var("OIDC_AUTO_PROVISION")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| "OIDC_AUTO_PROVISION must be true or false".to_string())?,
+            oidc_groups_claim: env::var("OIDC_GROUPS_CLAIM")
+                .unwrap_or_else(|_| "groups".to_string()),
+            oidc_admin_group: env::var("OIDC_ADMIN_GROUP").ok(),
+            password_min_length: env::var("PASSWORD_MIN_LENGTH")
+                .unwrap_or_else(|_| "8".to_string())
+                .parse()
+                .map_err(|_| "PASSWORD_MIN_LENGTH must be a valid number".to_string())?,
+            password_min_character_classes: env::var("PASSWORD_MIN_CHARACTER_CLASSES")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .map_err(|_| "PASSWORD_MIN_CHARACTER_CLASSES must be a valid number".to_string())?,
+            password_check_common: env::var("PASSWORD_CHECK_COMMON")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .map_err(|_| "PASSWORD_CHECK_COMMON must be true or false".to_string())?,
+            password_denylist_file: env::var("PASSWORD_DENYLIST_FILE").ok(),
+            password_hash_algorithm,
+            bcrypt_cost: env::var("BCRYPT_COST")
+                .unwrap_or_else(|_| bcrypt::DEFAULT_COST.to_string())
+                .parse()
+                .map_err(|_| "BCRYPT_COST must be a valid number".to_string())?,
+            argon2_memory_kib: env::var("ARGON2_MEMORY_KIB")
+                .unwrap_or_else(|_| "19456".to_string())
+                .parse()
+                .map_err(|_| "ARGON2_MEMORY_KIB must be a valid number".to_string())?,
+            argon2_iterations: env::var("ARGON2_ITERATIONS")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .map_err(|_| "ARGON2_ITERATIONS must be a valid number".to_string())?,
+            argon2_parallelism: env::var("ARGON2_PARALLELISM")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .map_err(|_| "ARGON2_PARALLELISM must be a valid number".to_string())?,
+            login_lockout_threshold: env::var("LOGIN_LOCKOUT_THRESHOLD")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse()
+                .map_err(|_| "LOGIN_LOCKOUT_THRESHOLD must be a valid number".to_string())?,
+            login_lockout_base_minutes: env::var("LOGIN_LOCKOUT_BASE_MINUTES")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .map_err(|_| "LOGIN_LOCKOUT_BASE_MINUTES must be a valid number".to_string())?,
+            login_lockout_max_minutes: env::var("LOGIN_LOCKOUT_MAX_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .map_err(|_| "LOGIN_LOCKOUT_MAX_MINUTES must be a valid number".to_string())?,
+            invite_only_registration: env::var("INVITE_ONLY_REGISTRATION")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| "INVITE_ONLY_REGISTRATION must be true or false".to_string())?,
+            invite_expiration_days: env::var("INVITE_EXPIRATION_DAYS")
+                .unwrap_or_else(|_| "7".to_string())
+                .parse()
+                .map_err(|_| "INVITE_EXPIRATION_DAYS must be a valid number".to_string())?,
+            impersonation_token_minutes: env::var("IMPERSONATION_TOKEN_MINUTES")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()
+                .map_err(|_| "IMPERSONATION_TOKEN_MINUTES must be a valid number".to_string())?,
+            auth_cookies_enabled: env::var("AUTH_COOKIES_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .map_err(|_| "AUTH_COOKIES_ENABLED must be true or false".to_string())?,
+            auth_cookie_secure: env::var("AUTH_COOKIE_SECURE")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .map_err(|_| "AUTH_COOKIE_SECURE must be true or false".to_string())?,
+            auth_cookie_same_site: auth_cookie_same_site.to_string(),
+            auth_cookie_domain: env::var("AUTH_COOKIE_DOMAIN").ok(),
+            auth_cache_ttl_seconds: env::var("AUTH_CACHE_TTL_SECONDS")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .map_err(|_| "AUTH_CACHE_TTL_SECONDS must be a valid number".to_string())?,
+            block_done_with_open_dependencies: env::var("BLOCK_DONE_WITH_OPEN_DEPENDENCIES")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .map_err(|_| "BLOCK_DONE_WITH_OPEN_DEPENDENCIES must be true or false".to_string())?,
         })
     }
 }
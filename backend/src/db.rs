@@ -1,15 +1,47 @@
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use sqlx::{ConnectOptions, Connection};
+use std::str::FromStr;
 use crate::config::Config;
-use crate::error::Result;
+use crate::error::{AppError, Result};
 
 pub async fn init_db(config: &Config) -> Result<SqlitePool> {
+    // Las claves foráneas se activan explícitamente en cada conexión para que
+    // los `ON DELETE CASCADE` (tareas, sesiones, tokens...) se apliquen siempre.
+    let options = SqliteConnectOptions::from_str(&config.database_url)?.foreign_keys(true);
+
     // Conecta a SQLite (crea el archivo si no existe)
     let pool = SqlitePoolOptions::new()
         .max_connections(5)
-        .connect(&config.database_url)
+        .connect_with(options.clone())
         .await?;
 
     println!("->> DB | Conexión establecida a: {}", config.database_url);
 
+    run_migrations(options).await?;
+
     Ok(pool)
 }
+
+/// Aplica las migraciones pendientes en una conexión propia con las claves foráneas
+/// desactivadas: algunas reconstruyen tablas (`DROP` + `RENAME`) y, con ellas activas,
+/// el `DROP` borraría en cascada las filas que dependen de la tabla. `PRAGMA foreign_keys`
+/// no tiene efecto dentro de la transacción de cada migración, por eso se fija al conectar.
+async fn run_migrations(options: SqliteConnectOptions) -> Result<()> {
+    let mut conn = options.foreign_keys(false).connect().await?;
+
+    sqlx::migrate!("./migrations").run(&mut conn).await?;
+
+    // Sin claves foráneas nada impide dejar referencias rotas: se comprueban al terminar.
+    let violations = sqlx::query("PRAGMA foreign_key_check").fetch_all(&mut conn).await?;
+    conn.close().await?;
+
+    if !violations.is_empty() {
+        return Err(AppError::Database(format!(
+            "Las migraciones dejaron {} referencias rotas entre tablas",
+            violations.len()
+        )));
+    }
+
+    println!("->> DB | Migraciones aplicadas");
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -34,6 +34,12 @@ pub enum AppError {
     
     #[error("Error interno del servidor: {0}")]
     InternalServerError(String),
+
+    #[error("Demasiadas solicitudes: {message}")]
+    TooManyRequests {
+        message: String,
+        retry_after_seconds: u64,
+    },
     
     #[error("Error de JWT: {0}")]
     Jwt(#[from] jsonwebtoken::errors::Error),
@@ -76,6 +82,11 @@ pub struct ErrorPayload {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            Self::TooManyRequests { retry_after_seconds, .. } => Some(*retry_after_seconds),
+            _ => None,
+        };
+
         let (status_code, error_payload) = match self {
             Self::Database(msg) => {
                 eprintln!("❌ Error de base de datos: {}", msg);
@@ -168,6 +179,19 @@ impl IntoResponse for AppError {
                     },
                 )
             }
+            Self::TooManyRequests { message, .. } => {
+                eprintln!("⏳ Demasiadas solicitudes: {}", message);
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    ErrorPayload {
+                        error: ApiError {
+                            code: "TOO_MANY_REQUESTS".to_string(),
+                            message,
+                            fields: None,
+                        },
+                    },
+                )
+            }
             Self::Jwt(err) => {
                 eprintln!("🎫 Error de JWT: {}", err);
                 (
@@ -222,7 +246,11 @@ impl IntoResponse for AppError {
             }
         };
 
-        (status_code, Json(error_payload)).into_response()
+        let mut response = (status_code, Json(error_payload)).into_response();
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
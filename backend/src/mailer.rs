@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::{
+    auth::opaque::generate_opaque_token,
+    config::Config,
+    error::{AppError, Result},
+};
+
+/// Correo saliente en texto plano.
+#[derive(Debug, Clone)]
+pub struct MailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Abstracción del transporte de correo. Los handlers solo conocen este trait,
+/// de modo que en local y en pruebas se puede usar el "outbox" sin servidor SMTP.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: MailMessage) -> Result<()>;
+}
+
+/// Envía los correos a través de un servidor SMTP.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &Config) -> Result<Self> {
+        let host = config
+            .smtp_host
+            .as_deref()
+            .ok_or_else(|| AppError::InternalServerError("SMTP_HOST must be set".to_string()))?;
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| AppError::InternalServerError(format!("Error configurando SMTP: {}", e)))?
+            .port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: parse_mailbox(&config.mail_from)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: MailMessage) -> Result<()> {
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(parse_mailbox(&message.to)?)
+            .subject(message.subject)
+            .body(message.body)
+            .map_err(|e| AppError::InternalServerError(format!("Error construyendo correo: {}", e)))?;
+
+        self.transport
+            .send(email)
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Error enviando correo: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+/// "Outbox" local: registra cada correo en el log y, si hay directorio configurado,
+/// lo guarda como archivo `.eml` para poder inspeccionarlo sin servidor de correo.
+pub struct OutboxMailer {
+    from: String,
+    dir: Option<PathBuf>,
+}
+
+impl OutboxMailer {
+    pub fn new(from: &str, dir: Option<PathBuf>) -> Self {
+        Self { from: from.to_string(), dir }
+    }
+}
+
+#[async_trait]
+impl Mailer for OutboxMailer {
+    async fn send(&self, message: MailMessage) -> Result<()> {
+        println!("->> MAILER | Correo para {}: {}", message.to, message.subject);
+
+        if let Some(dir) = &self.dir {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| AppError::InternalServerError(format!("Error creando outbox: {}", e)))?;
+
+            let file_name = format!("{}-{}.eml", Utc::now().format("%Y%m%dT%H%M%S%.f"), &generate_opaque_token()[..8]);
+            let contents = format!(
+                "From: {}\nTo: {}\nSubject: {}\nDate: {}\n\n{}\n",
+                self.from,
+                message.to,
+                message.subject,
+                Utc::now().to_rfc2822(),
+                message.body
+            );
+
+            tokio::fs::write(dir.join(file_name), contents)
+                .await
+                .map_err(|e| AppError::InternalServerError(format!("Error escribiendo outbox: {}", e)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Construye el transporte de correo indicado por `MAIL_TRANSPORT`.
+pub fn mailer_from_config(config: &Config) -> Result<Arc<dyn Mailer>> {
+    match config.mail_transport.as_str() {
+        "smtp" => Ok(Arc::new(SmtpMailer::new(config)?)),
+        "outbox" => Ok(Arc::new(OutboxMailer::new(
+            &config.mail_from,
+            config.mail_outbox_dir.as_ref().map(PathBuf::from),
+        ))),
+        other => Err(AppError::InternalServerError(format!(
+            "Transporte de correo desconocido: '{}' (MAIL_TRANSPORT debe ser smtp u outbox)",
+            other
+        ))),
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox> {
+    address
+        .parse()
+        .map_err(|_| AppError::BadRequest(format!("Dirección de correo inválida: {}", address)))
+}
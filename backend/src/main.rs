@@ -2,8 +2,12 @@
 // Este import es necesario para la línea `axum::serve` al final del archivo.
 
 
-use axum::{http::Method, middleware, Router};
+use axum::{
+    http::{header, header::InvalidHeaderValue, HeaderName, HeaderValue, Method},
+    middleware, Router,
+};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
 
@@ -20,24 +24,38 @@ mod auth;
 mod config;
 mod db;
 mod error;
+mod mailer;
 mod models;
 mod routes;
 mod security;
+mod tasks;
 
 #[cfg(test)]
 mod tests;
 
 // --- IMPORTS DE COMPONENTES ---
+use crate::auth::cache::TtlCache;
 use crate::auth::JwtService;
 use crate::config::Config;
 use crate::error::ErrorPayload;
+use crate::mailer::Mailer;
+use crate::security::permissions::Permission;
 use crate::security::rate_limit_middleware;
 
 // Se importan TODOS los modelos que se usarán en la documentación de la API.
 use crate::models::{
-    CreateTaskRequest, LoginRequest, LoginResponse, PaginationInfo, RegisterRequest,
-    SystemStats, Task, TaskPriorityStats, TaskQueryParams, TaskStatusStats, TasksResponse,
-    UpdateTaskRequest, User, UserSummary, UsersResponse, RecentActivity
+    CreateTaskRequest, ForgotPasswordRequest, Jwk, JwksResponse, LoginRequest, LoginResponse, LogoutRequest, PaginationInfo, RefreshTokenRequest,
+    RefreshTokenResponse, RegisterRequest, ResendVerificationRequest, ResetPasswordRequest, SystemStats, Task, TaskPriorityStats, TaskQueryParams,
+    TaskStatusStats, TasksResponse,
+    UpdateTaskRequest, User, UserSummary, UsersResponse, RecentActivity, VerifyEmailRequest,
+    LoginOutcome, RecoveryCodesResponse, TwoFactorChallengeResponse, TwoFactorCodeRequest, TwoFactorLoginRequest,
+    TwoFactorSetupResponse, PersonalAccessToken, CreatePersonalAccessTokenRequest, CreatedPersonalAccessTokenResponse,
+    UserSession, OidcAuthorizeResponse, OidcCallbackRequest, UpdateProfileRequest, ChangePasswordRequest,
+    DeleteAccountRequest, AccountExport, LoginHistoryEntry, LinkedIdentity, Invite, CreateInviteRequest, CreatedInviteResponse,
+    RegistrationSettings, Role, PermissionInfo, CreateRoleRequest, UpdateRoleRequest, ImpersonationResponse,
+    AuditLogEntry, AuditLogQueryParams, UpdateUserStatusRequest, Tag, TagsInput, RenameTagRequest, MergeTagsRequest,
+    TaskMember, AddAssigneeRequest, AddWatcherRequest, ChecklistItem, CreateChecklistItemRequest, UpdateChecklistItemRequest,
+    AddDependencyRequest, RecurrenceInput
 };
 
 
@@ -46,7 +64,16 @@ use crate::models::{
 pub struct AppState {
     pub db_pool: sqlx::SqlitePool,
     pub jwt_service: JwtService,
+    pub mailer: Arc<dyn Mailer>,
     pub config: Config,
+    /// Versión de token vigente por usuario (ver `auth::token_versions`).
+    pub token_versions: TtlCache<i32, i64>,
+    /// Permisos concedidos por cada rol.
+    pub role_permissions: TtlCache<String, Vec<Permission>>,
+    /// Si el `jti` de un access token está revocado (ver `auth::revocation`).
+    pub revoked_jtis: TtlCache<String, bool>,
+    /// Sesiones abiertas con la actividad ya registrada (ver `auth::sessions`).
+    pub open_sessions: TtlCache<String, ()>,
 }
 
 
@@ -56,18 +83,77 @@ pub struct AppState {
     paths(
         // Rutas existentes
         routes::root_handler,
+        routes::get_jwks,
         routes::register_user,
         routes::login_user,
+        routes::login_two_factor,
+        routes::oidc_authorize,
+        routes::oidc_callback,
+        routes::refresh_token,
+        routes::logout_user,
+        routes::forgot_password,
+        routes::reset_password,
+        routes::verify_email,
+        routes::resend_verification_email,
         routes::get_current_user,
+        routes::update_current_user,
+        routes::change_password,
+        routes::export_current_user_data,
+        routes::delete_current_user,
+        routes::setup_two_factor,
+        routes::confirm_two_factor,
+        routes::regenerate_two_factor_recovery_codes,
+        routes::disable_two_factor_auth,
+        routes::get_personal_tokens,
+        routes::create_personal_access_token,
+        routes::delete_personal_access_token,
+        routes::get_sessions,
+        routes::delete_session,
+        routes::delete_other_sessions,
         routes::get_tasks,
         routes::create_task,
         routes::get_task,
         routes::update_task,
         routes::delete_task,
+        routes::add_assignee,
+        routes::remove_assignee,
+        routes::watch_task,
+        routes::unwatch_task,
+        routes::add_watcher,
+        routes::remove_watcher,
+        routes::add_task_dependency,
+        routes::remove_task_dependency,
+        routes::set_task_recurrence,
+        routes::stop_task_recurrence,
+        routes::create_checklist_item,
+        routes::update_checklist_item_handler,
+        routes::delete_checklist_item_handler,
+        routes::get_tags,
+        routes::rename_tag_handler,
+        routes::merge_tags_handler,
+        routes::delete_tag_handler,
         // --- NUEVAS RUTAS DE ADMIN ---
         routes::get_all_users,
         routes::get_user_tasks,
         routes::get_system_stats,
+        routes::delete_user,
+        routes::export_user_data,
+        routes::unlock_user,
+        routes::create_registration_invite,
+        routes::get_invites,
+        routes::delete_invite,
+        routes::get_registration_settings,
+        routes::update_registration_settings,
+        routes::get_roles,
+        routes::create_custom_role,
+        routes::update_custom_role,
+        routes::delete_custom_role,
+        routes::get_permissions,
+        routes::suspend_user,
+        routes::deactivate_user,
+        routes::reactivate_user,
+        routes::impersonate_user,
+        routes::get_audit_log,
     ),
     components(
         schemas(
@@ -81,6 +167,57 @@ pub struct AppState {
             TaskQueryParams,
             TasksResponse,
             LoginResponse,
+            RefreshTokenRequest,
+            RefreshTokenResponse,
+            LogoutRequest,
+            ForgotPasswordRequest,
+            ResetPasswordRequest,
+            VerifyEmailRequest,
+            ResendVerificationRequest,
+            LoginOutcome,
+            TwoFactorChallengeResponse,
+            TwoFactorLoginRequest,
+            TwoFactorCodeRequest,
+            TwoFactorSetupResponse,
+            RecoveryCodesResponse,
+            PersonalAccessToken,
+            CreatePersonalAccessTokenRequest,
+            CreatedPersonalAccessTokenResponse,
+            UserSession,
+            OidcAuthorizeResponse,
+            OidcCallbackRequest,
+            UpdateProfileRequest,
+            ChangePasswordRequest,
+            DeleteAccountRequest,
+            AccountExport,
+            LoginHistoryEntry,
+            LinkedIdentity,
+            Invite,
+            CreateInviteRequest,
+            CreatedInviteResponse,
+            RegistrationSettings,
+            Role,
+            PermissionInfo,
+            CreateRoleRequest,
+            UpdateRoleRequest,
+            ImpersonationResponse,
+            AuditLogEntry,
+            AuditLogQueryParams,
+            UpdateUserStatusRequest,
+            Tag,
+            TagsInput,
+            RenameTagRequest,
+            MergeTagsRequest,
+            TaskMember,
+            AddAssigneeRequest,
+            AddWatcherRequest,
+            AddDependencyRequest,
+            RecurrenceInput,
+            ChecklistItem,
+            CreateChecklistItemRequest,
+            UpdateChecklistItemRequest,
+            Jwk,
+            JwksResponse,
             ErrorPayload,
             PaginationInfo,
             // --- NUEVOS MODELOS DE ADMIN ---
@@ -97,6 +234,7 @@ pub struct AppState {
         (name = "API Status", description = "Operaciones para chequear el estado de la API"),
         (name = "Authentication", description = "Endpoints para registro, login y gestión de usuarios"),
         (name = "Tasks", description = "Gestión completa de tareas"),
+        (name = "Tags", description = "Catálogo de tags de cada usuario"),
         (name = "Admin", description = "Operaciones exclusivas para administradores")
     ),
     info(
@@ -135,28 +273,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 2. Inicializar base de datos
     let db_pool = db::init_db(&config).await?;
 
-    // 3. Inicializar el servicio JWT
-    let jwt_service = JwtService::new(&config.jwt_secret, config.jwt_expiration_hours);
+    // 3. Inicializar el servicio JWT (y la rotación de claves si es asimétrico)
+    let jwt_service = JwtService::from_config(&config, &db_pool).await?;
+    auth::spawn_key_rotation(jwt_service.clone(), db_pool.clone());
+
+    // 4. Purgar periódicamente los tokens revocados o expirados
+    auth::revocation::spawn_token_pruner(db_pool.clone(), config.token_prune_interval_minutes);
 
-    // 4. Crear el estado compartido de la aplicación
+    // 5. Crear el estado compartido de la aplicación
     let app_state = AppState {
         db_pool,
         jwt_service,
+        mailer: mailer::mailer_from_config(&config)?,
         config: config.clone(),
+        token_versions: TtlCache::new(config.auth_cache_ttl_seconds),
+        role_permissions: TtlCache::new(config.auth_cache_ttl_seconds),
+        revoked_jtis: TtlCache::new(config.auth_cache_ttl_seconds),
+        open_sessions: TtlCache::new(auth::sessions::open_sessions_ttl(&config)),
     };
 
-    // --- 5. CONSTRUIR EL ROUTER CON LAS CAPAS DE SEGURIDAD (MIDDLEWARE) ---
+    // --- 6. CONSTRUIR EL ROUTER CON LAS CAPAS DE SEGURIDAD (MIDDLEWARE) ---
     let app = Router::new()
         .route("/", axum::routing::get(routes::root_handler))
+        .route("/.well-known/jwks.json", axum::routing::get(routes::get_jwks))
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .nest("/api/v1", routes::api_router())
         .layer(
             // El orden de las capas (layers) es importante. Se aplican de abajo hacia arriba.
             // Primero el CORS para permitir peticiones desde orígenes diferentes.
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-                .allow_headers(Any),
+            cors_layer(&config)?,
         )
         // Después, el rate limiting para proteger todos los endpoints contra ataques de fuerza bruta.
         .layer(
@@ -164,7 +309,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .with_state(app_state);
 
-    // 6. Iniciar el servidor
+    // 7. Iniciar el servidor
     let server_address_str = format!("{}:{}", config.host, config.port);
     let addr: SocketAddr = server_address_str.parse()?;
 
@@ -187,3 +332,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Con las sesiones por cookies, el navegador solo las envía si CORS permite credenciales,
+/// lo que exige un origen concreto (el del frontend) en lugar de `*`.
+fn cors_layer(config: &Config) -> Result<CorsLayer, InvalidHeaderValue> {
+    let methods = [Method::GET, Method::POST, Method::PUT, Method::DELETE];
+    if !config.auth_cookies_enabled {
+        return Ok(CorsLayer::new().allow_origin(Any).allow_methods(methods).allow_headers(Any));
+    }
+
+    Ok(CorsLayer::new()
+        .allow_origin(HeaderValue::from_str(config.app_base_url.trim_end_matches('/'))?)
+        .allow_methods(methods)
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static(auth::cookies::CSRF_HEADER),
+        ])
+        .allow_credentials(true))
+}
@@ -13,17 +13,31 @@ use serde_json::json;
     "name": "Jesús Farfán Luna",
     "email": "lic.farfanluna@hotmail.com",
     "role": "user",
-    "created_at": "2025-08-20T10:00:00Z"
+    "created_at": "2025-08-20T10:00:00Z",
+    "email_verified_at": "2025-08-20T10:05:00Z",
+    "totp_enabled_at": null,
+    "status": "active",
+    "status_reason": null
 }))]
 pub struct User {
     pub id: i32,
     pub name: String,
     pub email: String,
-    /// Rol del usuario: 'user' o 'admin'
+    /// Rol del usuario: 'user', 'admin' o un rol personalizado
     pub role: String,
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub created_at: String,
+    /// Fecha de verificación del email (null si aún no se ha verificado)
+    pub email_verified_at: Option<String>,
+    /// Fecha de activación de la autenticación en dos pasos (null si está desactivada)
+    pub totp_enabled_at: Option<String>,
+    /// Estado de la cuenta: 'active', 'suspended' o 'deactivated'
+    pub status: String,
+    /// Motivo indicado al suspender, desactivar o reactivar la cuenta
+    pub status_reason: Option<String>,
+    #[serde(skip_serializing)]
+    pub token_version: i64,
 }
 
 /// Representa los datos del usuario devueltos en el login.
@@ -34,6 +48,7 @@ pub struct UserLoginResponse {
     pub email: String,
     pub role: String,
     pub created_at: String,
+    pub email_verified_at: Option<String>,
 }
 
 /// Representa una tarea perteneciente a un usuario.
@@ -49,6 +64,16 @@ pub struct UserLoginResponse {
     "created_at": "2025-08-20T12:00:00Z",
     "updated_at": "2025-08-20T14:30:00Z",
     "tags": "rust,api,documentacion",
+    "assignees": [{ "id": 2, "name": "Admin User" }],
+    "watchers": [{ "id": 3, "name": "Super Admin" }],
+    "parent_id": null,
+    "subtasks_total": 5,
+    "subtasks_done": 3,
+    "checklist": [{ "id": 1, "title": "Documentar los endpoints de tareas", "done": true }],
+    "blocked_by": [100],
+    "blocked": true,
+    "recurrence_id": 7,
+    "recurrence": "FREQ=WEEKLY;INTERVAL=1;BYDAY=MO",
     "owner_name": "Jesús Farfán Luna",
     "owner_email": "lic.farfanluna@hotmail.com"
 }))]
@@ -63,10 +88,35 @@ pub struct Task {
     pub created_at: String,
     pub updated_at: String,
     pub tags: Option<String>,
-    pub assigned_to: Option<String>,
+    /// Personas asignadas, en el orden en que se añadieron.
+    #[sqlx(json)]
+    pub assignees: Vec<TaskMember>,
+    /// Usuarios que siguen la tarea.
+    #[sqlx(json)]
+    pub watchers: Vec<TaskMember>,
+    /// Tarea de la que esta es una subtarea.
+    pub parent_id: Option<i32>,
+    /// Progreso de las subtareas directas: `subtasks_done` de `subtasks_total` terminadas.
+    pub subtasks_total: i64,
+    pub subtasks_done: i64,
+    #[sqlx(json)]
+    pub checklist: Vec<ChecklistItem>,
+    /// IDs de las tareas que bloquean a esta.
+    #[sqlx(json)]
+    pub blocked_by: Vec<i32>,
+    /// Si alguna de las tareas de `blocked_by` sigue sin terminar.
+    pub blocked: bool,
+    /// Serie de tareas repetitivas a la que pertenece.
+    pub recurrence_id: Option<i32>,
+    /// Regla RRULE de la serie mientras siga activa.
+    pub recurrence: Option<String>,
     // Campos adicionales para administradores
     pub owner_name: Option<String>,
     pub owner_email: Option<String>,
+    /// Subtareas anidadas; solo con `include_subtasks=true`.
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subtasks: Option<Vec<Task>>,
 }
 
 /// Parámetros de consulta para filtrar y paginar tareas con búsqueda avanzada.
@@ -142,9 +192,29 @@ pub struct TaskQueryParams {
     #[schema(example = "admin@admin.com")]
     pub owner_email: Option<String>,
     
-    /// Filtrar por persona asignada a la tarea.
+    /// Filtrar por nombre de la persona asignada, o `unassigned` para las tareas sin asignar.
     #[schema(example = "Jesús Farfán")]
     pub assigned_to: Option<String>,
+    
+    /// Tareas asignadas a alguno de estos usuarios: `me` o IDs, separados por comas.
+    #[schema(example = "me,2")]
+    pub assignee: Option<String>,
+    
+    /// Tareas seguidas por alguno de estos usuarios: `me` o IDs, separados por comas.
+    #[schema(example = "me")]
+    pub watcher: Option<String>,
+    
+    /// Devolver solo las tareas que no son subtareas de otra.
+    #[schema(example = true)]
+    pub top_level: Option<bool>,
+    
+    /// Incluir en cada tarea sus subtareas anidadas en `subtasks`.
+    #[schema(example = true)]
+    pub include_subtasks: Option<bool>,
+    
+    /// Filtrar por tareas bloqueadas (`true`) o sin bloqueos abiertos (`false`).
+    #[schema(example = false)]
+    pub blocked: Option<bool>,
 }
 
 // --- Nuevos modelos para administración ---
@@ -172,6 +242,7 @@ pub struct UsersResponse {
     "name": "Jesús Farfán Luna",
     "email": "lic.farfanluna@hotmail.com",
     "role": "user",
+    "status": "active",
     "task_count": 12,
     "created_at": "2025-08-20T10:00:00Z"
 }))]
@@ -180,6 +251,7 @@ pub struct UserSummary {
     pub name: String,
     pub email: String,
     pub role: String,
+    pub status: String,
     pub task_count: i64,
     pub created_at: String,
 }
@@ -251,8 +323,11 @@ pub struct UpdateUserRoleRequest {
 pub struct LoginRequest {
     #[validate(email(message = "Invalid email format"))]
     pub email: String,
-    #[validate(length(min = 6, message = "Password must be at least 6 characters"))]
+    #[validate(length(min = 1, message = "Password is required"))]
     pub password: String,
+    /// Entrega la sesión en cookies HttpOnly (requiere `AUTH_COOKIES_ENABLED`)
+    #[serde(default)]
+    pub use_cookies: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
@@ -266,8 +341,11 @@ pub struct RegisterRequest {
     pub name: String,
     #[validate(email(message = "Invalid email format"))]
     pub email: String,
-    #[validate(length(min = 6, max = 100, message = "Password must be between 6 and 100 characters"))]
+    #[validate(length(min = 1, max = 100, message = "Password is required and must be at most 100 characters"))]
     pub password: String,
+    /// Código de invitación (obligatorio si el registro es solo por invitación)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<String>,
 }
 
 #[derive(Deserialize, Debug, ToSchema, Validate)]
@@ -277,7 +355,8 @@ pub struct RegisterRequest {
     "status": "todo",
     "priority": "high",
     "due_date": "2025-08-22T23:59:59Z",
-    "tags": "rust,api,documentacion"
+    "tags": "rust,api,documentacion",
+    "assignees": [2]
 }))]
 pub struct CreateTaskRequest {
     #[validate(length(min = 3, max = 120, message = "Title must be between 3 and 120 characters"))]
@@ -289,9 +368,15 @@ pub struct CreateTaskRequest {
     pub priority: Option<String>,
     #[validate(custom(function = "validate_due_date"))]
     pub due_date: Option<String>,
-    #[validate(length(max = 500, message = "Tags cannot exceed 500 characters"))]
-    pub tags: Option<String>,
-    pub assigned_to: Option<String>,
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Option<TagsInput>,
+    /// IDs de las personas asignadas; deben tener la cuenta activa.
+    #[validate(custom(function = "validate_assignees"))]
+    pub assignees: Option<Vec<i32>>,
+    /// Tarea de la que cuelga la nueva subtarea.
+    pub parent_id: Option<i32>,
+    /// Regla de repetición; exige `due_date`.
+    pub recurrence: Option<RecurrenceInput>,
 }
 
 #[derive(Deserialize, Debug, ToSchema, Validate)]
@@ -301,7 +386,8 @@ pub struct CreateTaskRequest {
     "status": "doing",
     "priority": "high",
     "due_date": "2025-08-25T23:59:59Z",
-    "tags": "rust,api,documentacion,urgente"
+    "tags": "rust,api,documentacion,urgente",
+    "assignees": [2, 3]
 }))]
 pub struct UpdateTaskRequest {
     #[validate(length(min = 3, max = 120, message = "Title must be between 3 and 120 characters"))]
@@ -313,9 +399,11 @@ pub struct UpdateTaskRequest {
     pub priority: Option<String>,
     #[validate(custom(function = "validate_due_date"))]
     pub due_date: Option<String>,
-    #[validate(length(max = 500, message = "Tags cannot exceed 500 characters"))]
-    pub tags: Option<String>,
-    pub assigned_to: Option<String>,
+    #[validate(custom(function = "validate_tags"))]
+    pub tags: Option<TagsInput>,
+    /// IDs de las personas asignadas; deben tener la cuenta activa.
+    #[validate(custom(function = "validate_assignees"))]
+    pub assignees: Option<Vec<i32>>,
 }
 
 /// Respuesta paginada para las tareas
@@ -346,6 +434,7 @@ pub struct PaginationInfo {
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 #[schema(example = json!({
     "token": "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...",
+    "refresh_token": "4f1c2d9e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e",
     "user": {
         "id": 1,
         "name": "Jesús Farfán Luna",
@@ -355,11 +444,719 @@ pub struct PaginationInfo {
     }
 }))]
 pub struct LoginResponse {
+    /// Vacío (y omitido) si la sesión se entregó en cookies
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub token: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub refresh_token: String,
     pub user: UserLoginResponse,
+    /// Token CSRF de la sesión con cookies; se envía en la cabecera `X-CSRF-Token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csrf_token: Option<String>,
+}
+
+/// Petición para rotar un refresh token. Con sesión en cookies se omite el cuerpo.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "refresh_token": "4f1c2d9e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e"
+}))]
+pub struct RefreshTokenRequest {
+    #[validate(length(min = 1, message = "Refresh token is required"))]
+    pub refresh_token: String,
+}
+
+/// Petición opcional de logout. Si incluye el refresh token, también se revoca.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "refresh_token": "4f1c2d9e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e"
+}))]
+pub struct LogoutRequest {
+    pub refresh_token: Option<String>,
+}
+
+/// Petición para solicitar un correo de restablecimiento de contraseña.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "email": "lic.farfanluna@hotmail.com"
+}))]
+pub struct ForgotPasswordRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+}
+
+/// Petición para establecer una nueva contraseña con un token de restablecimiento.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "token": "9a8f7e6d5c4b3a2f1e4f1c2d9e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b",
+    "new_password": "nuevo_password_seguro"
+}))]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1, message = "Reset token is required"))]
+    pub token: String,
+    #[validate(length(min = 1, max = 100, message = "Password is required and must be at most 100 characters"))]
+    pub new_password: String,
+}
+
+/// Resultado del login: la sesión completa, o el reto del segundo paso si la cuenta tiene 2FA.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(untagged)]
+pub enum LoginOutcome {
+    Authenticated(LoginResponse),
+    TwoFactorRequired(TwoFactorChallengeResponse),
+}
+
+/// Reto devuelto por el login cuando la cuenta tiene 2FA activado.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "two_factor_required": true,
+    "challenge_token": "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0",
+    "expires_in": 300
+}))]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    /// Segundos de validez del reto
+    pub expires_in: i64,
+}
+
+/// Segundo paso del login: reto recibido y código TOTP (o código de recuperación).
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "challenge_token": "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0",
+    "code": "123456"
+}))]
+pub struct TwoFactorLoginRequest {
+    #[validate(length(min = 1, message = "Challenge token is required"))]
+    pub challenge_token: String,
+    #[validate(length(min = 1, max = 32, message = "Code is required"))]
+    pub code: String,
+    /// Entrega la sesión en cookies HttpOnly (requiere `AUTH_COOKIES_ENABLED`)
+    #[serde(default)]
+    pub use_cookies: bool,
+}
+
+/// Código TOTP (o de recuperación) para confirmar operaciones de 2FA.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "code": "123456"
+}))]
+pub struct TwoFactorCodeRequest {
+    #[validate(length(min = 1, max = 32, message = "Code is required"))]
+    pub code: String,
+}
+
+/// Datos para registrar la cuenta en una app de autenticación.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "secret": "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP",
+    "otpauth_url": "otpauth://totp/To-Do%20API:lic.farfanluna%40hotmail.com?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP&issuer=To-Do%20API"
+}))]
+pub struct TwoFactorSetupResponse {
+    pub secret: String,
+    pub otpauth_url: String,
+}
+
+/// Códigos de recuperación de un solo uso. Solo se muestran al generarlos.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "recovery_codes": ["3f9a1-c27d0", "8b4e2-19fa7"]
+}))]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Token de acceso personal (sin el valor secreto).
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[schema(example = json!({
+    "id": 1,
+    "name": "CI pipeline",
+    "token_prefix": "tdp_3f9a1c2d",
+    "scopes": ["tasks:read", "tasks:write"],
+    "expires_at": "2026-01-20T10:00:00Z",
+    "last_used_at": null,
+    "created_at": "2025-10-20T10:00:00Z"
+}))]
+pub struct PersonalAccessToken {
+    pub id: i32,
+    pub name: String,
+    /// Primeros caracteres del token, para reconocerlo
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_at: String,
+}
+
+/// Petición para crear un token de acceso personal.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "name": "CI pipeline",
+    "scopes": ["tasks:read", "tasks:write"],
+    "expires_in_days": 90
+}))]
+pub struct CreatePersonalAccessTokenRequest {
+    #[validate(length(min = 1, max = 100, message = "Name must be between 1 and 100 characters"))]
+    pub name: String,
+    /// Permisos: 'tasks:read', 'tasks:write', 'admin'
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<String>,
+    /// Días de validez (sin valor, el token no caduca)
+    #[validate(range(min = 1, max = 365, message = "Expiration must be between 1 and 365 days"))]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Token personal recién creado. `token` solo se muestra en esta respuesta.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CreatedPersonalAccessTokenResponse {
+    pub token: String,
+    pub personal_access_token: PersonalAccessToken,
+}
+
+/// Sesión abierta en un dispositivo.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "id": "5b1f0c2e9d8a7b6c5d4e3f2a1b0c9d8e7f6a5b4c3d2e1f0a9b8c7d6e5f4a3b2c",
+    "device": "Firefox en Linux",
+    "ip_address": "192.168.1.20",
+    "user_agent": "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+    "created_at": "2025-08-20T10:00:00Z",
+    "last_active_at": "2025-08-21T08:30:00Z",
+    "current": true
+}))]
+pub struct UserSession {
+    pub id: String,
+    /// Navegador y sistema operativo deducidos del user agent
+    pub device: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+    pub last_active_at: String,
+    /// Indica si es la sesión desde la que se hace la petición
+    pub current: bool,
+}
+
+/// URL del proveedor OpenID Connect a la que redirigir al usuario.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "authorization_url": "https://idp.example.com/authorize?response_type=code&client_id=todo-backend&state=7c1d...&code_challenge=...&code_challenge_method=S256",
+    "state": "7c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d"
+}))]
+pub struct OidcAuthorizeResponse {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+/// Parámetros recibidos por el frontend en la redirección del proveedor.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "code": "SplxlOBeZQQYbYS6WxSbIA",
+    "state": "7c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d"
+}))]
+pub struct OidcCallbackRequest {
+    #[validate(length(min = 1, message = "Authorization code is required"))]
+    pub code: String,
+    #[validate(length(min = 1, message = "State is required"))]
+    pub state: String,
+    /// Entrega la sesión en cookies HttpOnly (requiere `AUTH_COOKIES_ENABLED`)
+    #[serde(default)]
+    pub use_cookies: bool,
+}
+
+/// Cambios de perfil del usuario autenticado. Cambiar el email exige la contraseña actual.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "name": "Lic. Farfan Luna",
+    "email": "nuevo@admin.com",
+    "current_password": "demo123"
+}))]
+pub struct UpdateProfileRequest {
+    #[validate(length(min = 2, max = 100, message = "Name must be between 2 and 100 characters"))]
+    pub name: Option<String>,
+    #[validate(email(message = "Invalid email format"))]
+    pub email: Option<String>,
+    pub current_password: Option<String>,
+}
+
+/// Petición para cambiar la contraseña conociendo la actual.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "current_password": "demo123",
+    "new_password": "nuevo_password_seguro"
+}))]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1, message = "Current password is required"))]
+    pub current_password: String,
+    #[validate(length(min = 1, max = 100, message = "Password is required and must be at most 100 characters"))]
+    pub new_password: String,
+}
+
+/// Confirmación con contraseña para eliminar la propia cuenta.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "password": "demo123"
+}))]
+pub struct DeleteAccountRequest {
+    #[validate(length(min = 1, message = "Password is required"))]
+    pub password: String,
+}
+
+/// Intento de inicio de sesión registrado para la cuenta.
+#[derive(Serialize, Deserialize, Debug, ToSchema, sqlx::FromRow)]
+pub struct LoginHistoryEntry {
+    pub ip_address: String,
+    pub success: bool,
+    pub user_agent: Option<String>,
+    pub attempted_at: Option<String>,
+}
+
+/// Identidad de un proveedor externo (OpenID Connect) vinculada a la cuenta.
+#[derive(Serialize, Deserialize, Debug, ToSchema, sqlx::FromRow)]
+pub struct LinkedIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: Option<String>,
+    pub last_login_at: Option<String>,
+}
+
+/// Exportación completa de los datos personales de un usuario.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AccountExport {
+    pub exported_at: String,
+    pub profile: User,
+    pub tasks: Vec<Task>,
+    pub login_history: Vec<LoginHistoryEntry>,
+    pub sessions: Vec<UserSession>,
+    pub personal_access_tokens: Vec<PersonalAccessToken>,
+    pub linked_identities: Vec<LinkedIdentity>,
+}
+
+/// Invitación de registro emitida por un administrador.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "id": 1,
+    "code_prefix": "3f9a1c2b",
+    "email": "nuevo@empresa.com",
+    "role": "user",
+    "status": "pending",
+    "created_by": 2,
+    "expires_at": "2025-08-27T10:00:00Z",
+    "used_at": null,
+    "used_by": null,
+    "created_at": "2025-08-20T10:00:00Z"
+}))]
+pub struct Invite {
+    pub id: i32,
+    /// Primeros caracteres del código, para reconocerlo
+    pub code_prefix: String,
+    /// Si se indica, solo esta dirección puede usar la invitación
+    pub email: Option<String>,
+    /// Rol asignado a la cuenta creada
+    pub role: String,
+    /// Estado: 'pending', 'used', 'revoked' o 'expired'
+    pub status: String,
+    pub created_by: Option<i32>,
+    pub expires_at: String,
+    pub used_at: Option<String>,
+    pub used_by: Option<i32>,
+    pub created_at: String,
+}
+
+/// Petición para crear una invitación.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "email": "nuevo@empresa.com",
+    "role": "user",
+    "expires_in_days": 7
+}))]
+pub struct CreateInviteRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: Option<String>,
+    /// Rol de la cuenta creada (por defecto 'user')
+    #[validate(custom(function = "validate_role"))]
+    pub role: Option<String>,
+    /// Días de validez (por defecto `INVITE_EXPIRATION_DAYS`)
+    #[validate(range(min = 1, max = 90, message = "Expiration must be between 1 and 90 days"))]
+    pub expires_in_days: Option<i64>,
+}
+
+/// Invitación recién creada. `code` solo se muestra en esta respuesta.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CreatedInviteResponse {
+    pub code: String,
+    pub invite: Invite,
+}
+
+/// Modo de registro vigente.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "invite_only": true
+}))]
+pub struct RegistrationSettings {
+    /// Si está activo, `/auth/register` exige un código de invitación
+    pub invite_only: bool,
+}
+
+/// Rol con los permisos que concede.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "name": "auditor",
+    "description": "Auditor de solo lectura",
+    "is_system": false,
+    "permissions": ["stats:read", "tasks:read_all", "users:read"],
+    "user_count": 2,
+    "created_at": "2025-08-20T10:00:00Z"
+}))]
+pub struct Role {
+    pub name: String,
+    pub description: Option<String>,
+    /// Los roles de sistema ('user', 'admin') no pueden modificarse ni eliminarse
+    pub is_system: bool,
+    pub permissions: Vec<String>,
+    /// Cuentas que tienen asignado el rol
+    pub user_count: i64,
+    pub created_at: String,
+}
+
+/// Permiso asignable a un rol.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "name": "tasks:read_all",
+    "description": "Consultar las tareas de todos los usuarios"
+}))]
+pub struct PermissionInfo {
+    pub name: String,
+    pub description: String,
+}
+
+/// Petición para crear un rol personalizado.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "name": "support",
+    "description": "Soporte: consulta y edita tareas ajenas",
+    "permissions": ["tasks:read_all", "tasks:update_all", "users:read"]
+}))]
+pub struct CreateRoleRequest {
+    #[validate(custom(function = "validate_role"))]
+    pub name: String,
+    #[validate(length(max = 200, message = "Description cannot exceed 200 characters"))]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+/// Petición para modificar un rol personalizado. Los permisos se sustituyen por completo.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "description": "Auditor de solo lectura",
+    "permissions": ["tasks:read_all", "stats:read"]
+}))]
+pub struct UpdateRoleRequest {
+    #[validate(length(max = 200, message = "Description cannot exceed 200 characters"))]
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+/// Motivo de un cambio de estado de una cuenta.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "reason": "Uso indebido reportado por soporte"
+}))]
+pub struct UpdateUserStatusRequest {
+    #[validate(length(min = 1, max = 500, message = "Reason must be between 1 and 500 characters"))]
+    pub reason: Option<String>,
+}
+
+/// Token emitido para actuar temporalmente como otro usuario.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "token": "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...",
+    "expires_at": "2025-08-20T10:15:00Z",
+    "user": {
+        "id": 5,
+        "name": "Usuario Afectado",
+        "email": "usuario@empresa.com",
+        "role": "user",
+        "created_at": "2025-08-18T10:00:00Z",
+        "email_verified_at": null,
+        "totp_enabled_at": null
+    }
+}))]
+pub struct ImpersonationResponse {
+    /// Access token del usuario suplantado, sin refresh token
+    pub token: String,
+    pub expires_at: String,
+    pub user: User,
+}
+
+/// Entrada del registro de auditoría.
+#[derive(Serialize, Deserialize, Debug, ToSchema, sqlx::FromRow)]
+#[schema(example = json!({
+    "id": 42,
+    "actor_id": 2,
+    "user_id": 5,
+    "action": "impersonation.request",
+    "method": "GET",
+    "path": "/api/v1/tasks",
+    "ip_address": "127.0.0.1",
+    "created_at": "2025-08-20T10:01:00Z"
+}))]
+pub struct AuditLogEntry {
+    pub id: i64,
+    /// Quién realizó la acción (null si la cuenta se eliminó)
+    pub actor_id: Option<i32>,
+    /// Usuario afectado o suplantado
+    pub user_id: Option<i32>,
+    /// 'impersonation.started', 'impersonation.request', 'user.suspended', 'user.deactivated' o 'user.reactivated'
+    pub action: String,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,
+}
+
+/// Filtros del registro de auditoría.
+#[derive(Deserialize, Debug, ToSchema, IntoParams)]
+pub struct AuditLogQueryParams {
+    pub actor_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub action: Option<String>,
+    /// Máximo de entradas (por defecto 100, máximo 500)
+    pub limit: Option<i64>,
+}
+
+/// Tags de una tarea: una lista de nombres o, por compatibilidad, un string separado por comas.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[serde(untagged)]
+pub enum TagsInput {
+    #[schema(example = json!(["rust", "api"]))]
+    List(Vec<String>),
+    #[schema(example = "rust,api")]
+    Csv(String),
+}
+
+/// Tag del catálogo de un usuario con el número de tareas que lo usan.
+#[derive(Serialize, Deserialize, Debug, ToSchema, sqlx::FromRow)]
+#[schema(example = json!({
+    "id": 7,
+    "name": "api",
+    "task_count": 4,
+    "created_at": "2025-08-20T10:00:00Z"
+}))]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub task_count: i64,
+    pub created_at: String,
+}
+
+/// Petición para cambiar el nombre de un tag.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "name": "backend"
+}))]
+pub struct RenameTagRequest {
+    #[validate(length(min = 1, max = 50, message = "Tag name must be between 1 and 50 characters"))]
+    pub name: String,
+}
+
+/// Petición para fusionar un tag en otro.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "target_id": 3
+}))]
+pub struct MergeTagsRequest {
+    /// Tag que se conserva; el de la ruta desaparece
+    pub target_id: i64,
+}
+
+/// Usuario asignado a una tarea o que la sigue.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[schema(example = json!({
+    "id": 2,
+    "name": "Admin User"
+}))]
+pub struct TaskMember {
+    pub id: i32,
+    pub name: String,
+}
+
+/// Elemento de la lista de comprobación de una tarea.
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, sqlx::FromRow)]
+#[schema(example = json!({
+    "id": 1,
+    "title": "Documentar los endpoints de tareas",
+    "done": false
+}))]
+pub struct ChecklistItem {
+    pub id: i64,
+    pub title: String,
+    pub done: bool,
+}
+
+/// Petición para añadir un elemento a la lista de comprobación.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "title": "Documentar los endpoints de tareas"
+}))]
+pub struct CreateChecklistItemRequest {
+    #[validate(length(min = 1, max = 200, message = "Checklist item must be between 1 and 200 characters"))]
+    pub title: String,
+}
+
+/// Petición para editar un elemento de la lista de comprobación; los campos omitidos no cambian.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "done": true
+}))]
+pub struct UpdateChecklistItemRequest {
+    #[validate(length(min = 1, max = 200, message = "Checklist item must be between 1 and 200 characters"))]
+    pub title: Option<String>,
+    pub done: Option<bool>,
+}
+
+/// Petición para bloquear una tarea hasta que otra se termine.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "blocker_id": 100
+}))]
+pub struct AddDependencyRequest {
+    /// Tarea que debe terminarse antes
+    pub blocker_id: i64,
+}
+
+/// Regla de repetición de una tarea: un RRULE o los campos estructurados equivalentes.
+/// `count` y `until` son excluyentes; sin ninguno, la serie no termina.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "frequency": "weekly",
+    "interval": 2,
+    "weekdays": ["MO", "TH"],
+    "count": 10
+}))]
+pub struct RecurrenceInput {
+    /// Regla en formato RRULE, p. ej. `FREQ=MONTHLY;BYMONTHDAY=31;UNTIL=20271231`; sustituye a los demás campos.
+    pub rrule: Option<String>,
+    /// `daily`, `weekly` o `monthly`
+    pub frequency: Option<String>,
+    /// Cada cuántos días, semanas o meses se repite (por defecto 1)
+    pub interval: Option<u32>,
+    /// Días de la semana (`MO`..`SU`) de una regla semanal
+    pub weekdays: Option<Vec<String>>,
+    /// Día del mes de una regla mensual; en los meses más cortos se usa el último día
+    pub month_day: Option<u32>,
+    /// Número total de tareas de la serie
+    pub count: Option<u32>,
+    /// Fecha límite (RFC 3339)
+    pub until: Option<String>,
+}
+
+/// Petición para añadir una persona asignada a una tarea.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "user_id": 2
+}))]
+pub struct AddAssigneeRequest {
+    pub user_id: i32,
+}
+
+/// Petición para que otro usuario siga una tarea.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "user_id": 3
+}))]
+pub struct AddWatcherRequest {
+    pub user_id: i32,
+}
+
+/// Petición para verificar un email con el token recibido por correo.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "token": "9a8f7e6d5c4b3a2f1e4f1c2d9e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b"
+}))]
+pub struct VerifyEmailRequest {
+    #[validate(length(min = 1, message = "Verification token is required"))]
+    pub token: String,
+}
+
+/// Petición para reenviar el correo de verificación.
+#[derive(Serialize, Deserialize, Debug, ToSchema, Validate)]
+#[schema(example = json!({
+    "email": "lic.farfanluna@hotmail.com"
+}))]
+pub struct ResendVerificationRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+}
+
+/// Nuevo par de tokens devuelto al rotar un refresh token.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[schema(example = json!({
+    "token": "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9...",
+    "refresh_token": "9a8f7e6d5c4b3a2f1e4f1c2d9e8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b"
+}))]
+pub struct RefreshTokenResponse {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub token: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub refresh_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub csrf_token: Option<String>,
+}
+
+/// Clave pública en formato JWK (RFC 7517).
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[schema(example = json!({
+    "kty": "OKP",
+    "kid": "3f9a1c2b7d4e5f60",
+    "use": "sig",
+    "alg": "EdDSA",
+    "crv": "Ed25519",
+    "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+}))]
+pub struct Jwk {
+    pub kty: String,
+    pub kid: String,
+    #[serde(rename = "use")]
+    pub key_use: String,
+    pub alg: String,
+    /// Módulo RSA (solo RS256).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// Exponente RSA (solo RS256).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    /// Curva (solo EdDSA).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    /// Clave pública Ed25519 (solo EdDSA).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+}
+
+/// Conjunto de claves públicas publicado en `/.well-known/jwks.json`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct JwksResponse {
+    pub keys: Vec<Jwk>,
 }
 
 // --- Validadores ---
+fn validate_assignees(assignees: &[i32]) -> Result<(), validator::ValidationError> {
+    if assignees.len() > crate::tasks::assignment::MAX_ASSIGNEES_PER_TASK {
+        return Err(validator::ValidationError::new("too_many_assignees"));
+    }
+    Ok(())
+}
+
+fn validate_tags(tags: &TagsInput) -> Result<(), validator::ValidationError> {
+    use crate::tasks::tags::{check_tag_name, parse_tags, MAX_TAGS_PER_TASK};
+    let tags = parse_tags(tags);
+    if tags.len() > MAX_TAGS_PER_TASK || tags.iter().any(|tag| check_tag_name(tag).is_err()) {
+        return Err(validator::ValidationError::new("invalid_tags"));
+    }
+    Ok(())
+}
+
 fn validate_status(status: &str) -> Result<(), validator::ValidationError> {
     match status {
         "todo" | "doing" | "done" => Ok(()),
@@ -374,11 +1171,13 @@ fn validate_priority(priority: &str) -> Result<(), validator::ValidationError> {
     }
 }
 
+/// Valida el formato del nombre de un rol; su existencia se comprueba en la base de datos.
 fn validate_role(role: &str) -> Result<(), validator::ValidationError> {
-    match role {
-        "user" | "admin" => Ok(()),
-        _ => Err(validator::ValidationError::new("invalid_role")),
+    let valid_chars = role.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if role.is_empty() || role.len() > 50 || !valid_chars {
+        return Err(validator::ValidationError::new("invalid_role"));
     }
+    Ok(())
 }
 
 fn validate_due_date(date_str: &str) -> Result<(), validator::ValidationError> {
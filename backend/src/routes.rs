@@ -1,7 +1,8 @@
 use axum::{
     extract::{ConnectInfo, Path, Query, State},
-    http::{HeaderMap, StatusCode},
-    routing::{get, post, put},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use chrono::Utc;
@@ -9,14 +10,67 @@ use std::net::SocketAddr;
 use validator::Validate;
 
 use crate::auth::AuthenticatedUser;
-use crate::security::{AdminUser, AuthenticatedUserWithRole, record_login_attempt};
+use crate::auth::account::{
+    check_account_active, delete_account, export_account_data, set_account_status,
+    ACCOUNT_ACTIVE, ACCOUNT_DEACTIVATED, ACCOUNT_SUSPENDED,
+};
+use crate::auth::cookies::{
+    check_csrf, clear_session_cookies, ensure_cookies_enabled, read_cookie, session_cookies, ACCESS_COOKIE,
+    REFRESH_COOKIE,
+};
+use crate::auth::oidc::{begin_oidc_login, complete_oidc_login, resolve_oidc_user};
+use crate::auth::personal_tokens::{
+    create_personal_token, delete_personal_token, list_personal_tokens, TokenScope,
+};
+use crate::auth::impersonation::start_impersonation;
+use crate::auth::email_verification::{send_verification_email, verify_email_token};
+use crate::auth::invites::{
+    claim_invite, create_invite, is_invite_only_registration, list_invites, revoke_invite, set_invite_only_registration,
+};
+use crate::auth::password_reset::{consume_password_reset_token, issue_password_reset_token};
+use crate::auth::passwords::{check_password_policy, hash_password, needs_rehash, verify_password};
+use crate::auth::refresh::{
+    issue_refresh_token, revoke_refresh_token, revoke_user_refresh_tokens, rotate_refresh_token,
+};
+use crate::auth::revocation::revoke_jti;
+use crate::auth::token_versions::bump_token_version;
+use crate::auth::sessions::{
+    create_session, describe_device, list_sessions, revoke_session, revoke_user_sessions, touch_session,
+};
+use crate::auth::two_factor::{
+    begin_totp_enrollment, complete_two_factor_challenge, confirm_totp_enrollment, disable_two_factor,
+    issue_two_factor_challenge, regenerate_recovery_codes, two_factor_challenge_email, verify_second_factor,
+};
+use crate::security::{
+    AuthenticatedUserWithRole, Authorized, Permission, check_account_lockout, clear_failed_attempts, record_login_attempt,
+};
+use crate::security::permissions::{
+    load_role_permissions, ImpersonateUsers, ManageInvites, ManageRoles, ManageUsers, ReadAllTasks, ReadAuditLog,
+    ReadStats, ReadUsers,
+};
+use crate::security::audit::{list_audit_log, record_audit_event, AuditContext};
+use crate::security::roles::{create_role, delete_role, ensure_can_grant_role, list_permissions, list_roles, update_role};
 use crate::error::{AppError, Result};
+use crate::mailer::MailMessage;
 use crate::models::{
-    CreateTaskRequest, LoginRequest, LoginResponse, PaginationInfo, RegisterRequest, 
-    Task, TaskQueryParams, TasksResponse, UpdateTaskRequest, User, UserSummary, 
+    CreateTaskRequest, ForgotPasswordRequest, JwksResponse, LoginOutcome, LoginRequest, LoginResponse, LogoutRequest, PaginationInfo, RefreshTokenRequest,
+    RefreshTokenResponse, RegisterRequest, ResendVerificationRequest, ResetPasswordRequest, Task, TaskQueryParams, TasksResponse, UpdateTaskRequest, User, UserSummary, 
     UsersResponse, SystemStats, TaskStatusStats, TaskPriorityStats, RecentActivity, UserLoginResponse,
-    UpdateUserRoleRequest
+    UpdateUserRoleRequest, VerifyEmailRequest, RecoveryCodesResponse, TwoFactorChallengeResponse, TwoFactorCodeRequest,
+    TwoFactorLoginRequest, TwoFactorSetupResponse, PersonalAccessToken, CreatePersonalAccessTokenRequest,
+    CreatedPersonalAccessTokenResponse, UserSession, OidcAuthorizeResponse, OidcCallbackRequest, UpdateProfileRequest,
+    ChangePasswordRequest, DeleteAccountRequest, Invite, CreateInviteRequest, CreatedInviteResponse, RegistrationSettings,
+    Role, PermissionInfo, CreateRoleRequest, UpdateRoleRequest, ImpersonationResponse, AuditLogEntry, AuditLogQueryParams,
+    UpdateUserStatusRequest, Tag, TagsInput, RenameTagRequest, MergeTagsRequest, AddAssigneeRequest, AddWatcherRequest,
+    ChecklistItem, CreateChecklistItemRequest, UpdateChecklistItemRequest, AddDependencyRequest, RecurrenceInput
 };
+use crate::tasks::assignment::{add_task_assignee, add_task_watcher, remove_task_assignee, resolve_user_filter, set_task_assignees, set_watching};
+use crate::tasks::tags::{delete_tag, list_tags, merge_tags, parse_tags, prune_unused_tags, rename_tag, set_task_tags};
+use crate::tasks::checklist::{add_checklist_item, delete_checklist_item, update_checklist_item};
+use crate::tasks::dependencies::{add_dependency, open_blockers, remove_dependency};
+use crate::tasks::recurrence::{set_recurrence, spawn_next_occurrence, stop_recurrence, RecurrenceRule};
+use crate::tasks::subtasks::{attach_subtasks, ensure_subtask_depth};
+use crate::tasks::{fetch_task, push_task_read_access, TASK_ACCESS, TASK_COLUMNS, TASK_FROM, TASK_READ_ACCESS};
 use crate::AppState;
 use crate::security::get_real_ip;
 
@@ -24,6 +78,7 @@ use crate::security::get_real_ip;
 pub fn api_router() -> Router<AppState> {
     auth_routes()
         .merge(task_routes())
+        .merge(tag_routes())
         .merge(admin_routes())
 }
 
@@ -32,7 +87,26 @@ fn auth_routes() -> Router<AppState> {
     Router::new()
         .route("/auth/register", post(register_user))
         .route("/auth/login", post(login_user))
-        .route("/me", get(get_current_user))
+        .route("/auth/login/2fa", post(login_two_factor))
+        .route("/auth/oidc/authorize", get(oidc_authorize))
+        .route("/auth/oidc/callback", post(oidc_callback))
+        .route("/auth/refresh", post(refresh_token))
+        .route("/auth/logout", post(logout_user))
+        .route("/auth/password/forgot", post(forgot_password))
+        .route("/auth/password/reset", post(reset_password))
+        .route("/auth/verify-email", post(verify_email))
+        .route("/auth/verify-email/resend", post(resend_verification_email))
+        .route("/me", get(get_current_user).put(update_current_user).delete(delete_current_user))
+        .route("/me/export", get(export_current_user_data))
+        .route("/me/password", post(change_password))
+        .route("/me/2fa/setup", post(setup_two_factor))
+        .route("/me/2fa/confirm", post(confirm_two_factor))
+        .route("/me/2fa/recovery-codes", post(regenerate_two_factor_recovery_codes))
+        .route("/me/2fa/disable", post(disable_two_factor_auth))
+        .route("/me/tokens", get(get_personal_tokens).post(create_personal_access_token))
+        .route("/me/tokens/:id", delete(delete_personal_access_token))
+        .route("/me/sessions", get(get_sessions).delete(delete_other_sessions))
+        .route("/me/sessions/:id", delete(delete_session))
 }
 
 fn task_routes() -> Router<AppState> {
@@ -40,15 +114,46 @@ fn task_routes() -> Router<AppState> {
         .route("/tasks", get(get_tasks).post(create_task))
         .route("/tasks/stats", get(get_task_stats))
         .route("/tasks/:id", get(get_task).put(update_task).delete(delete_task))
+        .route("/tasks/:id/assignees", post(add_assignee))
+        .route("/tasks/:id/assignees/:user_id", delete(remove_assignee))
+        .route("/tasks/:id/watch", post(watch_task).delete(unwatch_task))
+        .route("/tasks/:id/watchers", post(add_watcher))
+        .route("/tasks/:id/watchers/:user_id", delete(remove_watcher))
+        .route("/tasks/:id/dependencies", post(add_task_dependency))
+        .route("/tasks/:id/dependencies/:blocker_id", delete(remove_task_dependency))
+        .route("/tasks/:id/recurrence", put(set_task_recurrence).delete(stop_task_recurrence))
+        .route("/tasks/:id/checklist", post(create_checklist_item))
+        .route("/tasks/:id/checklist/:item_id", put(update_checklist_item_handler).delete(delete_checklist_item_handler))
         .route("/users", get(get_users_for_assignment))
 }
 
+fn tag_routes() -> Router<AppState> {
+    Router::new()
+        .route("/tags", get(get_tags))
+        .route("/tags/:id", put(rename_tag_handler).delete(delete_tag_handler))
+        .route("/tags/:id/merge", post(merge_tags_handler))
+}
+
 fn admin_routes() -> Router<AppState> {
     Router::new()
         .route("/admin/users", get(get_all_users))
         .route("/admin/users/:id/tasks", get(get_user_tasks))
         .route("/admin/stats", get(get_system_stats))
         .route("/admin/users/:id/role", put(update_user_role))
+        .route("/admin/users/:id", delete(delete_user))
+        .route("/admin/users/:id/export", get(export_user_data))
+        .route("/admin/users/:id/unlock", post(unlock_user))
+        .route("/admin/users/:id/suspend", post(suspend_user))
+        .route("/admin/users/:id/deactivate", post(deactivate_user))
+        .route("/admin/users/:id/reactivate", post(reactivate_user))
+        .route("/admin/users/:id/impersonate", post(impersonate_user))
+        .route("/admin/audit-log", get(get_audit_log))
+        .route("/admin/invites", get(get_invites).post(create_registration_invite))
+        .route("/admin/invites/:id", delete(delete_invite))
+        .route("/admin/settings/registration", get(get_registration_settings).put(update_registration_settings))
+        .route("/admin/roles", get(get_roles).post(create_custom_role))
+        .route("/admin/roles/:name", put(update_custom_role).delete(delete_custom_role))
+        .route("/admin/permissions", get(get_permissions))
         // Se elimina esta línea porque `GET /tasks` ya maneja el caso de admin
         // .route("/admin/tasks", get(get_all_tasks_admin))
 }
@@ -64,6 +169,13 @@ pub async fn root_handler() -> Json<serde_json::Value> {
     }))
 }
 
+/// Publica las claves públicas vigentes para que otros servicios validen nuestros tokens.
+/// Con HS256 el conjunto está vacío: el secreto compartido nunca se expone.
+#[utoipa::path(get, path = "/.well-known/jwks.json", tag = "Authentication", responses((status = 200, body = JwksResponse)))]
+pub async fn get_jwks(State(state): State<AppState>) -> Result<Json<JwksResponse>> {
+    Ok(Json(JwksResponse { keys: state.jwt_service.jwks(&state.db_pool).await? }))
+}
+
 /// (ADMIN) Actualiza el rol de un usuario.
 #[utoipa::path(
     put,
@@ -75,11 +187,12 @@ pub async fn root_handler() -> Json<serde_json::Value> {
 )]
 pub async fn update_user_role(
     State(state): State<AppState>,
-    _admin: AdminUser,
+    admin: Authorized<ManageUsers>,
     Path(user_id): Path<i32>,
     Json(payload): Json<UpdateUserRoleRequest>,
 ) -> Result<Json<User>> {
     payload.validate()?;
+    ensure_can_grant_role(&state, &admin, &payload.role).await?;
 
     // Verificar que el usuario existe
     let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
@@ -88,12 +201,16 @@ pub async fn update_user_role(
         .await?
         .ok_or_else(|| AppError::NotFound(format!("Usuario con ID {} no encontrado", user_id)))?;
 
-    // Actualizar el rol
+    // Tampoco se puede cambiar el rol de quien tiene permisos que el admin no tiene
+    ensure_can_grant_role(&state, &admin, &user.role).await?;
+
+    // Actualizar el rol; los tokens emitidos con el rol anterior dejan de ser válidos
     sqlx::query("UPDATE users SET role = ? WHERE id = ?")
         .bind(&payload.role)
         .bind(user_id)
         .execute(&state.db_pool)
         .await?;
+    bump_token_version(&state, user_id).await?;
 
     let updated_user = User {
         role: payload.role,
@@ -104,22 +221,357 @@ pub async fn update_user_role(
     Ok(Json(updated_user))
 }
 
+/// Elimina la cuenta de un usuario junto con todos sus datos.
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "ID del usuario a eliminar"))
+)]
+pub async fn delete_user(
+    State(state): State<AppState>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<i32>,
+) -> Result<StatusCode> {
+    let deleted = delete_account(&state, user_id).await?;
+
+    println!("->> HANDLER | Cuenta eliminada por admin (ID: {}): {} (ID: {})", admin.user_id, deleted.email, user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Descarga los datos personales de un usuario, igual que `/me/export`, para atender
+/// solicitudes de acceso recibidas por soporte. Queda registrado en `/admin/audit-log`.
+#[utoipa::path(
+    get,
+    path = "/admin/users/{id}/export",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "ID del usuario cuyos datos se exportan")),
+    responses((status = 200, body = AccountExport))
+)]
+pub async fn export_user_data(
+    State(state): State<AppState>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    let export = export_account_data(&state, user_id, None).await?;
+    record_audit_event(&state.db_pool, admin.user_id, user_id, "user.exported", AuditContext::default()).await?;
+    let disposition = format!("attachment; filename=\"todo-export-{}.json\"", user_id);
+
+    println!("->> HANDLER | Datos de la cuenta (ID: {}) exportados por admin (ID: {})", user_id, admin.user_id);
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)))
+}
+
+/// Levanta el bloqueo por intentos fallidos de una cuenta.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/unlock",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "ID del usuario a desbloquear"))
+)]
+pub async fn unlock_user(
+    State(state): State<AppState>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<i32>,
+) -> Result<StatusCode> {
+    let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Usuario con ID {} no encontrado", user_id)))?;
+
+    let cleared = clear_failed_attempts(&state, &email).await?;
+
+    println!("->> HANDLER | Cuenta {} desbloqueada por admin (ID: {}) ({} intentos limpiados)", email, admin.user_id, cleared);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Suspende una cuenta: se cierran sus sesiones y no puede volver a entrar hasta que se reactive.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/suspend",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    request_body = UpdateUserStatusRequest,
+    params(("id" = i32, Path, description = "ID del usuario a suspender"))
+)]
+pub async fn suspend_user(
+    State(state): State<AppState>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<i32>,
+    Json(payload): Json<UpdateUserStatusRequest>,
+) -> Result<Json<User>> {
+    change_user_status(&state, &admin, user_id, ACCOUNT_SUSPENDED, payload).await
+}
+
+/// Desactiva una cuenta (p. ej. una baja), conservando sus tareas.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/deactivate",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    request_body = UpdateUserStatusRequest,
+    params(("id" = i32, Path, description = "ID del usuario a desactivar"))
+)]
+pub async fn deactivate_user(
+    State(state): State<AppState>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<i32>,
+    Json(payload): Json<UpdateUserStatusRequest>,
+) -> Result<Json<User>> {
+    change_user_status(&state, &admin, user_id, ACCOUNT_DEACTIVATED, payload).await
+}
+
+/// Reactiva una cuenta suspendida o desactivada.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/reactivate",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    request_body = UpdateUserStatusRequest,
+    params(("id" = i32, Path, description = "ID del usuario a reactivar"))
+)]
+pub async fn reactivate_user(
+    State(state): State<AppState>,
+    admin: Authorized<ManageUsers>,
+    Path(user_id): Path<i32>,
+    Json(payload): Json<UpdateUserStatusRequest>,
+) -> Result<Json<User>> {
+    change_user_status(&state, &admin, user_id, ACCOUNT_ACTIVE, payload).await
+}
+
+async fn change_user_status(
+    state: &AppState,
+    admin: &AuthenticatedUserWithRole,
+    user_id: i32,
+    status: &str,
+    payload: UpdateUserStatusRequest,
+) -> Result<Json<User>> {
+    payload.validate()?;
+
+    let user = set_account_status(state, admin.user_id, user_id, status, payload.reason.as_deref()).await?;
+
+    println!("->> HANDLER | Estado de la cuenta (ID: {}) cambiado a '{}' por admin (ID: {})", user_id, status, admin.user_id);
+    Ok(Json(user))
+}
+
+/// Emite un token de corta duración para ver la API exactamente como la ve un usuario.
+/// Cada petición hecha con él queda registrada en `/admin/audit-log`.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/impersonate",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "ID del usuario a suplantar")),
+    responses((status = 200, body = ImpersonationResponse))
+)]
+pub async fn impersonate_user(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    admin: Authorized<ImpersonateUsers>,
+    Path(user_id): Path<i32>,
+) -> Result<Json<ImpersonationResponse>> {
+    admin.require_session()?;
+
+    let ip = get_real_ip(&addr, &headers);
+    let impersonation = start_impersonation(&state, admin.user_id, user_id, Some(&ip)).await?;
+
+    println!("->> HANDLER | Suplantación iniciada: admin (ID: {}) como usuario (ID: {})", admin.user_id, user_id);
+    Ok(Json(impersonation))
+}
+
+/// Consulta el registro de auditoría (suplantaciones y peticiones suplantadas).
+#[utoipa::path(get, path = "/admin/audit-log", tag = "Admin", security(("bearer_auth" = [])), params(AuditLogQueryParams))]
+pub async fn get_audit_log(
+    State(state): State<AppState>,
+    _admin: Authorized<ReadAuditLog>,
+    Query(params): Query<AuditLogQueryParams>,
+) -> Result<Json<Vec<AuditLogEntry>>> {
+    let entries = list_audit_log(&state.db_pool, &params).await?;
+    Ok(Json(entries))
+}
+
+/// Crea una invitación de registro, opcionalmente para un email y con un rol concretos.
+#[utoipa::path(
+    post,
+    path = "/admin/invites",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    request_body = CreateInviteRequest,
+    responses((status = 201, body = CreatedInviteResponse))
+)]
+pub async fn create_registration_invite(
+    State(state): State<AppState>,
+    admin: Authorized<ManageInvites>,
+    Json(payload): Json<CreateInviteRequest>,
+) -> Result<(StatusCode, Json<CreatedInviteResponse>)> {
+    payload.validate()?;
+
+    let role = payload.role.as_deref().unwrap_or("user");
+    ensure_can_grant_role(&state, &admin, role).await?;
+    let (code, invite) = create_invite(&state, admin.user_id, payload.email.as_deref(), role, payload.expires_in_days).await?;
+
+    println!("->> HANDLER | Invitación creada: (ID: {}, Role: {}) por admin (ID: {})", invite.id, invite.role, admin.user_id);
+    Ok((StatusCode::CREATED, Json(CreatedInviteResponse { code, invite })))
+}
+
+/// Lista las invitaciones de registro y su estado.
+#[utoipa::path(get, path = "/admin/invites", tag = "Admin", security(("bearer_auth" = [])))]
+pub async fn get_invites(
+    State(state): State<AppState>,
+    _admin: Authorized<ManageInvites>,
+) -> Result<Json<Vec<Invite>>> {
+    let invites = list_invites(&state).await?;
+    Ok(Json(invites))
+}
+
+/// Revoca una invitación que aún no se ha usado.
+#[utoipa::path(
+    delete,
+    path = "/admin/invites/{id}",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "ID de la invitación"))
+)]
+pub async fn delete_invite(
+    State(state): State<AppState>,
+    admin: Authorized<ManageInvites>,
+    Path(invite_id): Path<i32>,
+) -> Result<StatusCode> {
+    revoke_invite(&state, invite_id).await?;
+
+    println!("->> HANDLER | Invitación revocada: (ID: {}) por admin (ID: {})", invite_id, admin.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Indica si el registro público está cerrado.
+#[utoipa::path(get, path = "/admin/settings/registration", tag = "Admin", security(("bearer_auth" = [])))]
+pub async fn get_registration_settings(
+    State(state): State<AppState>,
+    _admin: Authorized<ManageInvites>,
+) -> Result<Json<RegistrationSettings>> {
+    let invite_only = is_invite_only_registration(&state).await?;
+    Ok(Json(RegistrationSettings { invite_only }))
+}
+
+/// Abre o cierra el registro público sin reiniciar el servidor.
+#[utoipa::path(put, path = "/admin/settings/registration", tag = "Admin", security(("bearer_auth" = [])), request_body = RegistrationSettings)]
+pub async fn update_registration_settings(
+    State(state): State<AppState>,
+    admin: Authorized<ManageInvites>,
+    Json(payload): Json<RegistrationSettings>,
+) -> Result<Json<RegistrationSettings>> {
+    set_invite_only_registration(&state, payload.invite_only).await?;
+
+    println!("->> HANDLER | Registro solo por invitación: {} (admin (ID: {}))", payload.invite_only, admin.user_id);
+    Ok(Json(payload))
+}
+
+/// Lista los roles con sus permisos y el número de cuentas que los tienen.
+#[utoipa::path(get, path = "/admin/roles", tag = "Admin", security(("bearer_auth" = [])))]
+pub async fn get_roles(
+    State(state): State<AppState>,
+    _admin: Authorized<ManageRoles>,
+) -> Result<Json<Vec<Role>>> {
+    let roles = list_roles(&state).await?;
+    Ok(Json(roles))
+}
+
+/// Crea un rol personalizado.
+#[utoipa::path(
+    post,
+    path = "/admin/roles",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    request_body = CreateRoleRequest,
+    responses((status = 201, body = Role))
+)]
+pub async fn create_custom_role(
+    State(state): State<AppState>,
+    admin: Authorized<ManageRoles>,
+    Json(payload): Json<CreateRoleRequest>,
+) -> Result<(StatusCode, Json<Role>)> {
+    payload.validate()?;
+
+    let role = create_role(&state, &payload.name, payload.description.as_deref(), &payload.permissions).await?;
+
+    println!("->> HANDLER | Rol creado: '{}' por admin (ID: {})", role.name, admin.user_id);
+    Ok((StatusCode::CREATED, Json(role)))
+}
+
+/// Sustituye la descripción y los permisos de un rol personalizado.
+#[utoipa::path(
+    put,
+    path = "/admin/roles/{name}",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    request_body = UpdateRoleRequest,
+    params(("name" = String, Path, description = "Nombre del rol"))
+)]
+pub async fn update_custom_role(
+    State(state): State<AppState>,
+    admin: Authorized<ManageRoles>,
+    Path(name): Path<String>,
+    Json(payload): Json<UpdateRoleRequest>,
+) -> Result<Json<Role>> {
+    payload.validate()?;
+
+    let role = update_role(&state, &name, payload.description.as_deref(), &payload.permissions).await?;
+
+    println!("->> HANDLER | Rol actualizado: '{}' por admin (ID: {})", role.name, admin.user_id);
+    Ok(Json(role))
+}
+
+/// Elimina un rol personalizado que no esté en uso.
+#[utoipa::path(
+    delete,
+    path = "/admin/roles/{name}",
+    tag = "Admin",
+    security(("bearer_auth" = [])),
+    params(("name" = String, Path, description = "Nombre del rol"))
+)]
+pub async fn delete_custom_role(
+    State(state): State<AppState>,
+    admin: Authorized<ManageRoles>,
+    Path(name): Path<String>,
+) -> Result<StatusCode> {
+    delete_role(&state, &name).await?;
+
+    println!("->> HANDLER | Rol eliminado: '{}' por admin (ID: {})", name, admin.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lista los permisos que se pueden asignar a un rol.
+#[utoipa::path(get, path = "/admin/permissions", tag = "Admin", security(("bearer_auth" = [])))]
+pub async fn get_permissions(
+    _admin: Authorized<ManageRoles>,
+) -> Json<Vec<PermissionInfo>> {
+    Json(list_permissions())
+}
+
 /// Obtiene estadísticas de tareas por estado para el usuario actual.
+/// Cuenta las mismas tareas que ve en `GET /tasks`: propias, asignadas y seguidas.
 #[utoipa::path(get, path = "/tasks/stats", tag = "Tasks", security(("bearer_auth" = [])))]
 pub async fn get_task_stats(
     State(state): State<AppState>,
     user: AuthenticatedUserWithRole,
 ) -> Result<Json<TaskStatusStats>> {
-    let mut query_builder = sqlx::QueryBuilder::new(
-        "SELECT 
-            SUM(CASE WHEN status = 'todo' THEN 1 ELSE 0 END) as todo,
-            SUM(CASE WHEN status = 'doing' THEN 1 ELSE 0 END) as doing,
-            SUM(CASE WHEN status = 'done' THEN 1 ELSE 0 END) as done
-         FROM tasks"
-    );
+    user.require_scope(TokenScope::TasksRead)?;
 
-    if !user.is_admin() {
-        query_builder.push(" WHERE user_id = ").push_bind(user.user_id);
+    let mut query_builder = sqlx::QueryBuilder::new(format!(
+        "SELECT 
+            SUM(CASE WHEN t.status = 'todo' THEN 1 ELSE 0 END) as todo,
+            SUM(CASE WHEN t.status = 'doing' THEN 1 ELSE 0 END) as doing,
+            SUM(CASE WHEN t.status = 'done' THEN 1 ELSE 0 END) as done
+         {} WHERE 1=1",
+        TASK_FROM
+    ));
+
+    if !user.has_permission(Permission::TasksReadAll) {
+        push_task_read_access(&mut query_builder, user.user_id);
     }
 
     let stats: TaskStatusStats = query_builder.build_query_as()
@@ -134,13 +586,16 @@ pub async fn get_task_stats(
 #[utoipa::path(get, path = "/users", tag = "Tasks", security(("bearer_auth" = [])))]
 pub async fn get_users_for_assignment(
     State(state): State<AppState>,
-    _user: AuthenticatedUser,
+    user: AuthenticatedUser,
 ) -> Result<Json<Vec<UserSummary>>> {
+    user.require_scope(TokenScope::TasksRead)?;
+
     let users: Vec<UserSummary> = sqlx::query_as(
-        "SELECT u.id, u.name, u.email, u.role, u.created_at,
+        "SELECT u.id, u.name, u.email, u.role, u.status, u.created_at,
          COUNT(t.id) as task_count
          FROM users u
          LEFT JOIN tasks t ON u.id = t.user_id
+         WHERE u.status = 'active'
          GROUP BY u.id
          ORDER BY u.name ASC"
     )
@@ -168,20 +623,50 @@ pub async fn register_user(
         return Err(AppError::Conflict("El email ya está registrado".to_string()));
     }
 
-    let password_hash = bcrypt::hash(&payload.password, bcrypt::DEFAULT_COST)?;
+    check_password_policy(&state.config, "password", &payload.password)?;
+
+    if payload.invite_code.is_none() && is_invite_only_registration(&state).await? {
+        return Err(AppError::Authentication("El registro solo está disponible por invitación".to_string()));
+    }
+
+    let password_hash = hash_password(&state.config, &payload.password)?;
+
+    // La invitación se consume en la misma transacción que crea la cuenta.
+    let mut tx = state.db_pool.begin().await?;
+
+    let (invite_id, role) = match &payload.invite_code {
+        Some(code) => {
+            let (invite_id, role) = claim_invite(&mut tx, code, &payload.email).await?;
+            (Some(invite_id), role)
+        }
+        None => (None, "user".to_string()),
+    };
 
-    let user_id = sqlx::query("INSERT INTO users (name, email, password_hash, role) VALUES (?, ?, ?, 'user')")
+    let user_id = sqlx::query("INSERT INTO users (name, email, password_hash, role) VALUES (?, ?, ?, ?)")
         .bind(&payload.name)
         .bind(&payload.email)
         .bind(&password_hash)
-        .execute(&state.db_pool)
+        .bind(&role)
+        .execute(&mut *tx)
         .await?
         .last_insert_rowid();
 
+    if let Some(invite_id) = invite_id {
+        sqlx::query("UPDATE invites SET used_by = ? WHERE id = ?")
+            .bind(user_id)
+            .bind(invite_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
     let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
         .bind(user_id)
         .fetch_one(&state.db_pool)
         .await?;
+
+    send_verification_email(&state, &user).await?;
         
     println!("->> HANDLER | Usuario registrado: {} (ID: {}, Role: {})", user.email, user_id, user.role);
     Ok((StatusCode::CREATED, Json(user)))
@@ -189,19 +674,33 @@ pub async fn register_user(
 
 
 /// Autentica a un usuario y devuelve un token JWT.
-#[utoipa::path(post, path = "/auth/login", tag = "Authentication", request_body = LoginRequest)]
+/// Si la cuenta tiene 2FA activado devuelve un reto para `/auth/login/2fa` en lugar de los tokens.
+/// Con `use_cookies` los tokens viajan en cookies HttpOnly y la respuesta solo trae el token CSRF.
+#[utoipa::path(
+    post,
+    path = "/auth/login",
+    tag = "Authentication",
+    request_body = LoginRequest,
+    responses((status = 200, body = LoginOutcome))
+)]
 pub async fn login_user(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     Json(payload): Json<LoginRequest>,
-) -> Result<Json<LoginResponse>> {
+) -> Result<(HeaderMap, Json<LoginOutcome>)> {
     payload.validate()?;
+    if payload.use_cookies {
+        ensure_cookies_enabled(&state.config)?;
+    }
 
     // Ahora que get_real_ip es pública, esto funcionará.
     let ip = get_real_ip(&addr, &headers);
     let user_agent = headers.get("user-agent").and_then(|h| h.to_str().ok());
 
+    // Los intentos contra una cuenta bloqueada no se registran, para no alargar el bloqueo.
+    check_account_lockout(&state, &payload.email).await?;
+
     let user_result = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ?")
         .bind(&payload.email)
         .fetch_optional(&state.db_pool)
@@ -215,14 +714,138 @@ pub async fn login_user(
         }
     };
 
-    if !bcrypt::verify(&payload.password, &user.password_hash)? {
+    if !verify_password(&payload.password, &user.password_hash)? {
         record_login_attempt(&state, &ip, Some(&payload.email), false, user_agent).await?;
         return Err(AppError::Authentication("Credenciales inválidas".to_string()));
     }
 
+    // Los hashes con parámetros antiguos se regeneran ahora que se conoce la contraseña.
+    if needs_rehash(&state.config, &user.password_hash) {
+        sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+            .bind(hash_password(&state.config, &payload.password)?)
+            .bind(user.id)
+            .execute(&state.db_pool)
+            .await?;
+        println!("->> AUTH | Hash de contraseña actualizado (ID: {})", user.id);
+    }
+
+    // Se comprueba después de la contraseña para no revelar qué cuentas están sin verificar.
+    if state.config.require_email_verification && user.email_verified_at.is_none() {
+        return Err(AppError::Authentication("Debes verificar tu email antes de iniciar sesión".to_string()));
+    }
+    check_account_active(&user.status)?;
+
+    // Con 2FA activado la contraseña solo da acceso al segundo paso; el login no cuenta como
+    // correcto (ni limpia los fallos de la cuenta) hasta que se valida el código.
+    if user.totp_enabled_at.is_some() {
+        let (challenge_token, expires_in) = issue_two_factor_challenge(&state, user.id).await?;
+        println!("->> HANDLER | Login pendiente de 2FA para: {}", user.email);
+        return Ok((HeaderMap::new(), Json(LoginOutcome::TwoFactorRequired(TwoFactorChallengeResponse {
+            two_factor_required: true,
+            challenge_token,
+            expires_in,
+        }))));
+    }
+
     record_login_attempt(&state, &ip, Some(&payload.email), true, user_agent).await?;
 
-    let token = state.jwt_service.generate_token(user.id)?;
+    let (cookies, response) = start_session(&state, user, &ip, user_agent, payload.use_cookies).await?;
+    Ok((cookies, Json(LoginOutcome::Authenticated(response))))
+}
+
+/// Segundo paso del login: canjea el reto con un código TOTP o de recuperación.
+#[utoipa::path(post, path = "/auth/login/2fa", tag = "Authentication", request_body = TwoFactorLoginRequest)]
+pub async fn login_two_factor(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<TwoFactorLoginRequest>,
+) -> Result<(HeaderMap, Json<LoginResponse>)> {
+    payload.validate()?;
+    if payload.use_cookies {
+        ensure_cookies_enabled(&state.config)?;
+    }
+
+    let ip = get_real_ip(&addr, &headers);
+    let user_agent = headers.get("user-agent").and_then(|h| h.to_str().ok());
+
+    // Los códigos erróneos cuentan para el bloqueo de la cuenta igual que las contraseñas,
+    // para que pedir un reto nuevo no permita seguir probando códigos.
+    let email = two_factor_challenge_email(&state, &payload.challenge_token).await?;
+    if let Some(email) = &email {
+        check_account_lockout(&state, email).await?;
+    }
+
+    let user_id = match complete_two_factor_challenge(&state, &payload.challenge_token, &payload.code).await {
+        Ok(user_id) => user_id,
+        Err(err) => {
+            record_login_attempt(&state, &ip, email.as_deref(), false, user_agent).await?;
+            return Err(err);
+        }
+    };
+
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    record_login_attempt(&state, &ip, Some(&user.email), true, user_agent).await?;
+
+    let (cookies, response) = start_session(&state, user, &ip, user_agent, payload.use_cookies).await?;
+    Ok((cookies, Json(response)))
+}
+
+/// Inicia el login con OpenID Connect (authorization code + PKCE).
+/// El frontend debe redirigir al usuario a `authorization_url`.
+#[utoipa::path(get, path = "/auth/oidc/authorize", tag = "Authentication", responses((status = 200, body = OidcAuthorizeResponse)))]
+pub async fn oidc_authorize(State(state): State<AppState>) -> Result<Json<OidcAuthorizeResponse>> {
+    let (authorization_url, login_state) = begin_oidc_login(&state).await?;
+    Ok(Json(OidcAuthorizeResponse { authorization_url, state: login_state }))
+}
+
+/// Completa el login con OpenID Connect con el `code` y el `state` devueltos por el proveedor.
+/// La autenticación en dos pasos la gestiona el proveedor, no se pide aquí.
+#[utoipa::path(post, path = "/auth/oidc/callback", tag = "Authentication", request_body = OidcCallbackRequest)]
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<OidcCallbackRequest>,
+) -> Result<(HeaderMap, Json<LoginResponse>)> {
+    payload.validate()?;
+    if payload.use_cookies {
+        ensure_cookies_enabled(&state.config)?;
+    }
+
+    let ip = get_real_ip(&addr, &headers);
+    let user_agent = headers.get("user-agent").and_then(|h| h.to_str().ok());
+
+    let identity = complete_oidc_login(&state, &payload.code, &payload.state).await?;
+    let user = match resolve_oidc_user(&state, &identity).await {
+        Ok(user) => user,
+        Err(err) => {
+            record_login_attempt(&state, &ip, identity.email.as_deref(), false, user_agent).await?;
+            return Err(err);
+        }
+    };
+
+    record_login_attempt(&state, &ip, Some(&user.email), true, user_agent).await?;
+    let (cookies, response) = start_session(&state, user, &ip, user_agent, payload.use_cookies).await?;
+    Ok((cookies, Json(response)))
+}
+
+/// Abre una sesión para el dispositivo y emite su par de tokens, en el cuerpo o en cookies.
+async fn start_session(
+    state: &AppState,
+    user: User,
+    ip: &str,
+    user_agent: Option<&str>,
+    use_cookies: bool,
+) -> Result<(HeaderMap, LoginResponse)> {
+    check_account_active(&user.status)?;
+    let session_id = create_session(state, user.id, ip, user_agent).await?;
+    let token = state.jwt_service.generate_token(&user, Some(&session_id))?;
+    let refresh_token = issue_refresh_token(state, user.id, Some(&session_id)).await?;
     
     let user_response = UserLoginResponse {
         id: user.id,
@@ -230,35 +853,617 @@ pub async fn login_user(
         email: user.email,
         role: user.role,
         created_at: user.created_at,
+        email_verified_at: user.email_verified_at,
     };
 
     // Se usa `{:?}` para imprimir el enum 'role', que deriva `Debug`
     println!("->> HANDLER | Login exitoso para: {} (Role: {:?})", user_response.email, user_response.role);
-    Ok(Json(LoginResponse { token, user: user_response }))
+    if use_cookies {
+        let (cookies, csrf_token) = session_cookies(&state.config, &token, &refresh_token)?;
+        return Ok((cookies, LoginResponse {
+            token: String::new(),
+            refresh_token: String::new(),
+            csrf_token: Some(csrf_token),
+            user: user_response,
+        }));
+    }
+
+    Ok((HeaderMap::new(), LoginResponse { token, refresh_token, csrf_token: None, user: user_response }))
 }
 
-/// Obtiene los datos del usuario actualmente autenticado.
-#[utoipa::path(get, path = "/me", tag = "Authentication", security(("bearer_auth" = [])))]
-pub async fn get_current_user(
+/// Rota un refresh token y devuelve un nuevo par de tokens.
+/// Reutilizar un refresh token ya rotado revoca todos los tokens de esa sesión.
+/// Sin cuerpo se usa la cookie de sesión, que exige la cabecera CSRF y se renueva en la respuesta.
+#[utoipa::path(post, path = "/auth/refresh", tag = "Authentication", request_body(content = Option<RefreshTokenRequest>))]
+pub async fn refresh_token(
     State(state): State<AppState>,
-    user: AuthenticatedUser,
-) -> Result<Json<User>> {
-    let user_data: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
-        .bind(user.user_id)
-        .fetch_one(&state.db_pool)
-        .await?;
-    Ok(Json(user_data))
-}
+    headers: HeaderMap,
+    payload: Option<Json<RefreshTokenRequest>>,
+) -> Result<(HeaderMap, Json<RefreshTokenResponse>)> {
+    let (presented, use_cookies) = match payload {
+        Some(Json(payload)) => {
+            payload.validate()?;
+            (payload.refresh_token, false)
+        }
+        None => {
+            ensure_cookies_enabled(&state.config)?;
+            let presented = read_cookie(&headers, REFRESH_COOKIE)
+                .ok_or_else(|| AppError::Authentication("Refresh token requerido".to_string()))?;
+            check_csrf(&headers)?;
+            (presented, true)
+        }
+    };
 
-// --- Handlers de Tareas (Con Lógica de Roles) ---
+    let (user_id, session_id, refresh_token) = rotate_refresh_token(&state, &presented).await?;
 
-/// Crea una nueva tarea.
-#[utoipa::path(post, path = "/tasks", tag = "Tasks", security(("bearer_auth" = [])), request_body = CreateTaskRequest)]
-pub async fn create_task(
+    // El nuevo access token lleva el rol y la versión de token actuales.
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .ok_or_else(|| AppError::Authentication("Usuario no encontrado en la base de datos".to_string()))?;
+    check_account_active(&user.status)?;
+    touch_session(&state, &session_id, None).await?;
+    let token = state.jwt_service.generate_token(&user, Some(&session_id))?;
+
+    println!("->> HANDLER | Refresh token rotado para usuario (ID: {})", user_id);
+    if use_cookies {
+        let (cookies, csrf_token) = session_cookies(&state.config, &token, &refresh_token)?;
+        return Ok((cookies, Json(RefreshTokenResponse {
+            token: String::new(),
+            refresh_token: String::new(),
+            csrf_token: Some(csrf_token),
+        })));
+    }
+
+    Ok((HeaderMap::new(), Json(RefreshTokenResponse { token, refresh_token, csrf_token: None })))
+}
+
+/// Cierra la sesión actual: revoca el access token, sus refresh tokens y, si se envía, el refresh token indicado.
+/// En una sesión con cookies también revoca el refresh token de la cookie y borra las cookies.
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "Authentication",
+    security(("bearer_auth" = [])),
+    request_body(content = Option<LogoutRequest>)
+)]
+pub async fn logout_user(
     State(state): State<AppState>,
     user: AuthenticatedUser,
+    headers: HeaderMap,
+    payload: Option<Json<LogoutRequest>>,
+) -> Result<(HeaderMap, StatusCode)> {
+    // Una suplantación también se da por terminada con logout.
+    if user.impersonator_id.is_none() {
+        user.require_session()?;
+    }
+
+    revoke_jti(&state, &user.jti, user.token_exp).await?;
+
+    if let Some(session_id) = &user.session_id {
+        match revoke_session(&state, user.user_id, session_id).await {
+            // Ya estaba cerrada (desde otro dispositivo o por un logout anterior): no es un error.
+            Ok(()) | Err(AppError::NotFound(_)) => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    if let Some(refresh_token) = payload.and_then(|Json(p)| p.refresh_token) {
+        revoke_refresh_token(&state, user.user_id, &refresh_token).await?;
+    }
+
+    let refresh_cookie = read_cookie(&headers, REFRESH_COOKIE);
+    if let Some(refresh_token) = &refresh_cookie {
+        revoke_refresh_token(&state, user.user_id, refresh_token).await?;
+    }
+
+    let cookies = if refresh_cookie.is_some() || read_cookie(&headers, ACCESS_COOKIE).is_some() {
+        clear_session_cookies(&state.config)?
+    } else {
+        HeaderMap::new()
+    };
+
+    println!("->> HANDLER | Logout de usuario (ID: {})", user.user_id);
+    Ok((cookies, StatusCode::NO_CONTENT))
+}
+
+/// Envía un correo con un enlace para restablecer la contraseña.
+/// Siempre responde 202, exista o no la cuenta, para no revelar qué emails están registrados.
+#[utoipa::path(post, path = "/auth/password/forgot", tag = "Authentication", request_body = ForgotPasswordRequest)]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> Result<StatusCode> {
+    payload.validate()?;
+
+    // La búsqueda y el envío se hacen en segundo plano: la respuesta tarda lo mismo exista o no
+    // la cuenta, y el tiempo de respuesta no revela qué emails están registrados.
+    tokio::spawn(async move {
+        if let Err(err) = send_password_reset_email(&state, &payload.email).await {
+            eprintln!("❌ Error enviando correo de restablecimiento a {}: {}", payload.email, err);
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn send_password_reset_email(state: &AppState, email: &str) -> Result<()> {
+    let user: Option<User> = sqlx::query_as("SELECT * FROM users WHERE email = ?")
+        .bind(email)
+        .fetch_optional(&state.db_pool)
+        .await?;
+
+    let Some(user) = user else {
+        return Ok(());
+    };
+
+    let token = issue_password_reset_token(state, user.id).await?;
+    let message = MailMessage {
+        to: user.email.clone(),
+        subject: "Restablece tu contraseña".to_string(),
+        body: format!(
+            "Hola {},\n\nPara elegir una nueva contraseña abre el siguiente enlace:\n{}/reset-password?token={}\n\nEl enlace caduca en {} minutos. Si no lo solicitaste, ignora este correo.",
+            user.name, state.config.app_base_url, token, state.config.password_reset_expiration_minutes
+        ),
+    };
+
+    state.mailer.send(message).await?;
+    println!("->> HANDLER | Restablecimiento de contraseña solicitado (ID: {})", user.id);
+    Ok(())
+}
+
+/// Establece una nueva contraseña usando un token de restablecimiento.
+/// Cierra todas las sesiones abiertas del usuario.
+#[utoipa::path(post, path = "/auth/password/reset", tag = "Authentication", request_body = ResetPasswordRequest)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<StatusCode> {
+    payload.validate()?;
+
+    check_password_policy(&state.config, "new_password", &payload.new_password)?;
+
+    let user_id = consume_password_reset_token(&state, &payload.token).await?;
+    let password_hash = hash_password(&state.config, &payload.new_password)?;
+
+    sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    revoke_user_sessions(&state, user_id, None).await?;
+    revoke_user_refresh_tokens(&state, user_id).await?;
+
+    // Quien restablece la contraseña demuestra ser el dueño del email: se levanta el bloqueo.
+    let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+    clear_failed_attempts(&state, &email).await?;
+
+    println!("->> HANDLER | Contraseña restablecida (ID: {})", user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Verifica el email de una cuenta usando el token enviado por correo.
+#[utoipa::path(post, path = "/auth/verify-email", tag = "Authentication", request_body = VerifyEmailRequest)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<StatusCode> {
+    payload.validate()?;
+
+    let user_id = verify_email_token(&state, &payload.token).await?;
+
+    println!("->> HANDLER | Email verificado (ID: {})", user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Reenvía el correo de verificación a una cuenta aún sin verificar.
+/// Siempre responde 202 para no revelar qué emails están registrados.
+#[utoipa::path(post, path = "/auth/verify-email/resend", tag = "Authentication", request_body = ResendVerificationRequest)]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(payload): Json<ResendVerificationRequest>,
+) -> Result<StatusCode> {
+    payload.validate()?;
+
+    let user: Option<User> = sqlx::query_as("SELECT * FROM users WHERE email = ? AND email_verified_at IS NULL")
+        .bind(&payload.email)
+        .fetch_optional(&state.db_pool)
+        .await?;
+
+    if let Some(user) = user {
+        send_verification_email(&state, &user).await?;
+        println!("->> HANDLER | Correo de verificación reenviado (ID: {})", user.id);
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Inicia la configuración de la autenticación en dos pasos.
+/// Devuelve el secreto y la URL `otpauth://`; se activa al confirmar un código.
+#[utoipa::path(post, path = "/me/2fa/setup", tag = "Authentication", security(("bearer_auth" = [])))]
+pub async fn setup_two_factor(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<TwoFactorSetupResponse>> {
+    user.require_session()?;
+
+    let (secret, otpauth_url) = begin_totp_enrollment(&state, user.user_id).await?;
+
+    println!("->> HANDLER | Configuración de 2FA iniciada (ID: {})", user.user_id);
+    Ok(Json(TwoFactorSetupResponse { secret, otpauth_url }))
+}
+
+/// Activa la autenticación en dos pasos y devuelve los códigos de recuperación.
+#[utoipa::path(post, path = "/me/2fa/confirm", tag = "Authentication", security(("bearer_auth" = [])), request_body = TwoFactorCodeRequest)]
+pub async fn confirm_two_factor(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>> {
+    user.require_session()?;
+
+    payload.validate()?;
+
+    let recovery_codes = confirm_totp_enrollment(&state, user.user_id, &payload.code).await?;
+
+    println!("->> HANDLER | 2FA activado (ID: {})", user.user_id);
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Genera nuevos códigos de recuperación; los anteriores dejan de ser válidos.
+#[utoipa::path(post, path = "/me/2fa/recovery-codes", tag = "Authentication", security(("bearer_auth" = [])), request_body = TwoFactorCodeRequest)]
+pub async fn regenerate_two_factor_recovery_codes(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>> {
+    user.require_session()?;
+
+    payload.validate()?;
+
+    if !verify_second_factor(&state, user.user_id, &payload.code).await? {
+        return Err(AppError::BadRequest("Código de verificación inválido".to_string()));
+    }
+    let recovery_codes = regenerate_recovery_codes(&state, user.user_id).await?;
+
+    println!("->> HANDLER | Códigos de recuperación regenerados (ID: {})", user.user_id);
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Desactiva la autenticación en dos pasos. No se permite a administradores si
+/// la política `REQUIRE_ADMIN_2FA` está activa.
+#[utoipa::path(post, path = "/me/2fa/disable", tag = "Authentication", security(("bearer_auth" = [])), request_body = TwoFactorCodeRequest)]
+pub async fn disable_two_factor_auth(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<TwoFactorCodeRequest>,
+) -> Result<StatusCode> {
+    user.require_session()?;
+
+    payload.validate()?;
+
+    if state.config.require_admin_2fa {
+        let role: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(user.user_id)
+            .fetch_one(&state.db_pool)
+            .await?;
+        if !load_role_permissions(&state.db_pool, &role).await?.is_empty() {
+            return Err(AppError::BadRequest(
+                "Los administradores deben mantener activada la autenticación en dos pasos".to_string(),
+            ));
+        }
+    }
+
+    if !verify_second_factor(&state, user.user_id, &payload.code).await? {
+        return Err(AppError::BadRequest("Código de verificación inválido".to_string()));
+    }
+    disable_two_factor(&state, user.user_id).await?;
+
+    println!("->> HANDLER | 2FA desactivado (ID: {})", user.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lista los tokens de acceso personales del usuario.
+#[utoipa::path(get, path = "/me/tokens", tag = "Authentication", security(("bearer_auth" = [])))]
+pub async fn get_personal_tokens(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<PersonalAccessToken>>> {
+    user.require_session()?;
+
+    let tokens = list_personal_tokens(&state, user.user_id).await?;
+    Ok(Json(tokens))
+}
+
+/// Crea un token de acceso personal con los scopes indicados.
+#[utoipa::path(
+    post,
+    path = "/me/tokens",
+    tag = "Authentication",
+    security(("bearer_auth" = [])),
+    request_body = CreatePersonalAccessTokenRequest
+)]
+pub async fn create_personal_access_token(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Json(payload): Json<CreatePersonalAccessTokenRequest>,
+) -> Result<(StatusCode, Json<CreatedPersonalAccessTokenResponse>)> {
+    user.require_session()?;
+    payload.validate()?;
+
+    let mut scopes = Vec::new();
+    for scope in &payload.scopes {
+        let scope = TokenScope::from_string(scope)
+            .ok_or_else(|| AppError::BadRequest(format!("Scope desconocido: {}", scope)))?;
+        if scope == TokenScope::Admin && !user.is_privileged() {
+            return Err(AppError::Authentication(
+                "Solo los roles con permisos pueden crear tokens con el scope 'admin'".to_string(),
+            ));
+        }
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let (token, personal_access_token) =
+        create_personal_token(&state, user.user_id, &payload.name, &scopes, payload.expires_in_days).await?;
+
+    println!("->> HANDLER | Token personal creado: (ID: {}) por usuario (ID: {})", personal_access_token.id, user.user_id);
+    Ok((StatusCode::CREATED, Json(CreatedPersonalAccessTokenResponse { token, personal_access_token })))
+}
+
+/// Revoca un token de acceso personal.
+#[utoipa::path(
+    delete,
+    path = "/me/tokens/{id}",
+    tag = "Authentication",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "ID del token"))
+)]
+pub async fn delete_personal_access_token(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(id): Path<i32>,
+) -> Result<StatusCode> {
+    user.require_session()?;
+
+    delete_personal_token(&state, user.user_id, id).await?;
+
+    println!("->> HANDLER | Token personal revocado: (ID: {}) por usuario (ID: {})", id, user.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lista las sesiones abiertas del usuario (dispositivo, IP y última actividad).
+#[utoipa::path(get, path = "/me/sessions", tag = "Authentication", security(("bearer_auth" = [])))]
+pub async fn get_sessions(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<UserSession>>> {
+    user.require_session()?;
+
+    let sessions = list_sessions(&state, user.user_id)
+        .await?
+        .into_iter()
+        .map(|session| UserSession {
+            current: user.session_id.as_deref() == Some(session.id.as_str()),
+            device: describe_device(session.user_agent.as_deref()),
+            id: session.id,
+            ip_address: session.ip_address,
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+            last_active_at: session.last_active_at,
+        })
+        .collect();
+
+    Ok(Json(sessions))
+}
+
+/// Cierra una sesión concreta (por ejemplo, la de un dispositivo perdido).
+#[utoipa::path(
+    delete,
+    path = "/me/sessions/{id}",
+    tag = "Authentication",
+    security(("bearer_auth" = [])),
+    params(("id" = String, Path, description = "ID de la sesión"))
+)]
+pub async fn delete_session(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode> {
+    user.require_session()?;
+
+    revoke_session(&state, user.user_id, &id).await?;
+
+    println!("->> HANDLER | Sesión cerrada remotamente por usuario (ID: {})", user.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Cierra todas las sesiones excepto la actual ("cerrar sesión en los demás dispositivos").
+#[utoipa::path(delete, path = "/me/sessions", tag = "Authentication", security(("bearer_auth" = [])))]
+pub async fn delete_other_sessions(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<StatusCode> {
+    user.require_session()?;
+
+    let revoked = revoke_user_sessions(&state, user.user_id, user.session_id.as_deref()).await?;
+
+    println!("->> HANDLER | {} sesiones cerradas por usuario (ID: {})", revoked, user.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Obtiene los datos del usuario actualmente autenticado.
+#[utoipa::path(get, path = "/me", tag = "Authentication", security(("bearer_auth" = [])))]
+pub async fn get_current_user(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<User>> {
+    let user_data: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user.user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+    Ok(Json(user_data))
+}
+
+/// Actualiza el nombre y/o el email del usuario autenticado.
+/// Un cambio de email exige la contraseña actual y vuelve a requerir verificación.
+#[utoipa::path(put, path = "/me", tag = "Authentication", security(("bearer_auth" = [])), request_body = UpdateProfileRequest)]
+pub async fn update_current_user(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<UpdateProfileRequest>,
+) -> Result<Json<User>> {
+    user.require_session()?;
+    payload.validate()?;
+
+    let current: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user.user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    let new_email = payload.email.as_deref().filter(|email| *email != current.email);
+    if let Some(email) = new_email {
+        let password = payload.current_password.as_deref().unwrap_or_default();
+        if !verify_password(password, &current.password_hash)? {
+            return Err(AppError::Authentication("La contraseña actual es incorrecta".to_string()));
+        }
+
+        if sqlx::query("SELECT id FROM users WHERE email = ?")
+            .bind(email)
+            .fetch_optional(&state.db_pool)
+            .await?
+            .is_some()
+        {
+            return Err(AppError::Conflict("El email ya está registrado".to_string()));
+        }
+    }
+
+    sqlx::query(
+        "UPDATE users SET
+            name = COALESCE(?, name),
+            email = COALESCE(?, email),
+            email_verified_at = CASE WHEN ? IS NULL THEN email_verified_at ELSE NULL END
+         WHERE id = ?"
+    )
+    .bind(&payload.name)
+    .bind(new_email)
+    .bind(new_email)
+    .bind(user.user_id)
+    .execute(&state.db_pool)
+    .await?;
+
+    let updated: User = sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user.user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    if new_email.is_some() {
+        send_verification_email(&state, &updated).await?;
+        println!("->> HANDLER | Email cambiado de {} a {} (ID: {})", current.email, updated.email, user.user_id);
+    }
+
+    println!("->> HANDLER | Perfil actualizado (ID: {})", user.user_id);
+    Ok(Json(updated))
+}
+
+/// Cambia la contraseña verificando la actual y cierra las demás sesiones.
+#[utoipa::path(post, path = "/me/password", tag = "Authentication", security(("bearer_auth" = [])), request_body = ChangePasswordRequest)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<StatusCode> {
+    user.require_session()?;
+    payload.validate()?;
+
+    let password_hash: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE id = ?")
+        .bind(user.user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    if !verify_password(&payload.current_password, &password_hash)? {
+        return Err(AppError::Authentication("La contraseña actual es incorrecta".to_string()));
+    }
+
+    check_password_policy(&state.config, "new_password", &payload.new_password)?;
+    let new_hash = hash_password(&state.config, &payload.new_password)?;
+    sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+        .bind(&new_hash)
+        .bind(user.user_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    let revoked = revoke_user_sessions(&state, user.user_id, user.session_id.as_deref()).await?;
+
+    println!("->> HANDLER | Contraseña cambiada (ID: {}), {} sesiones cerradas", user.user_id, revoked);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Descarga en JSON todos los datos personales del usuario: perfil, tareas,
+/// historial de inicios de sesión, sesiones, tokens personales e identidades vinculadas.
+#[utoipa::path(
+    get,
+    path = "/me/export",
+    tag = "Authentication",
+    security(("bearer_auth" = [])),
+    responses((status = 200, body = AccountExport))
+)]
+pub async fn export_current_user_data(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    user.require_session()?;
+
+    let export = export_account_data(&state, user.user_id, user.session_id.as_deref()).await?;
+    let disposition = format!("attachment; filename=\"todo-export-{}.json\"", user.user_id);
+
+    println!("->> HANDLER | Datos exportados (ID: {})", user.user_id);
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)))
+}
+
+/// Elimina definitivamente la cuenta del usuario tras confirmar su contraseña.
+#[utoipa::path(delete, path = "/me", tag = "Authentication", security(("bearer_auth" = [])), request_body = DeleteAccountRequest)]
+pub async fn delete_current_user(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(payload): Json<DeleteAccountRequest>,
+) -> Result<StatusCode> {
+    user.require_session()?;
+    payload.validate()?;
+
+    let password_hash: String = sqlx::query_scalar("SELECT password_hash FROM users WHERE id = ?")
+        .bind(user.user_id)
+        .fetch_one(&state.db_pool)
+        .await?;
+
+    if !verify_password(&payload.password, &password_hash)? {
+        return Err(AppError::Authentication("La contraseña es incorrecta".to_string()));
+    }
+
+    let deleted = delete_account(&state, user.user_id).await?;
+    revoke_jti(&state, &user.jti, user.token_exp).await?;
+
+    println!("->> HANDLER | Cuenta eliminada por el usuario: {} (ID: {})", deleted.email, user.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Handlers de Tareas (Con Lógica de Roles) ---
+
+/// Crea una nueva tarea. Con `parent_id` crea una subtarea, que pertenece al dueño de la tarea padre.
+#[utoipa::path(post, path = "/tasks", tag = "Tasks", security(("bearer_auth" = [])), request_body = CreateTaskRequest)]
+pub async fn create_task(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
     Json(payload): Json<CreateTaskRequest>,
 ) -> Result<(StatusCode, Json<Task>)> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
     payload.validate()?;
 
     if !state.config.allow_past_due_dates {
@@ -273,38 +1478,59 @@ pub async fn create_task(
         }
     }
     
+    let tags = payload.tags.as_ref().map(parse_tags).unwrap_or_default();
+    let mut assignees = payload.assignees.clone().unwrap_or_default();
+    let recurrence = payload.recurrence.as_ref().map(RecurrenceRule::from_input).transpose()?;
+
+    let mut tx = state.db_pool.begin().await?;
+
+    // Una subtarea exige poder editar la tarea padre. Si quien la crea no es el dueño, queda
+    // asignado a ella para no perder el acceso.
+    let owner_id = match payload.parent_id {
+        Some(parent_id) => {
+            let parent = find_accessible_task(&mut tx, &user, parent_id.into(), Permission::TasksUpdateAll).await?;
+            ensure_subtask_depth(&mut tx, parent_id.into()).await?;
+            if parent.user_id != user.user_id && !assignees.contains(&user.user_id) {
+                assignees.push(user.user_id);
+            }
+            parent.user_id
+        }
+        None => user.user_id,
+    };
+
     let task_id = sqlx::query(
-        "INSERT INTO tasks (user_id, title, description, status, priority, due_date, tags, assigned_to) 
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        "INSERT INTO tasks (user_id, title, description, status, priority, due_date, parent_id) 
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
     )
-        .bind(user.user_id)
+        .bind(owner_id)
         .bind(payload.title)
         .bind(payload.description)
         .bind(payload.status.unwrap_or_else(|| "todo".to_string()))
         .bind(payload.priority.unwrap_or_else(|| "med".to_string()))
         .bind(payload.due_date)
-        .bind(payload.tags)
-        .bind(payload.assigned_to)
-        .execute(&state.db_pool)
+        .bind(payload.parent_id)
+        .execute(&mut *tx)
         .await?
         .last_insert_rowid();
 
-    let task: Task = sqlx::query_as(
-        "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email
-         FROM tasks t
-         LEFT JOIN users u ON t.user_id = u.id
-         WHERE t.id = ?"
-    )
-        .bind(task_id)
-        .fetch_one(&state.db_pool)
-        .await?;
+    set_task_tags(&mut tx, task_id, owner_id, &tags).await?;
+    set_task_assignees(&mut tx, task_id, &assignees).await?;
+
+    let mut task = fetch_task(&mut tx, task_id).await?;
+    if let Some(rule) = &recurrence {
+        set_recurrence(&mut tx, &task, rule).await?;
+        task = fetch_task(&mut tx, task_id).await?;
+    }
+
+    tx.commit().await?;
     
     println!("->> HANDLER | Tarea creada: (ID: {}) por usuario (ID: {})", task.id, user.user_id);
     Ok((StatusCode::CREATED, Json(task)))
 }
 
 
-/// Obtiene la lista de tareas. Los usuarios normales solo ven sus tareas, los administradores ven todas.
+/// Obtiene la lista de tareas. Los usuarios normales ven las suyas y las que tienen asignadas,
+/// los administradores ven todas.
 #[utoipa::path(
     get,
     path = "/tasks",
@@ -317,6 +1543,8 @@ pub async fn get_tasks(
     user: AuthenticatedUserWithRole,
     Query(params): Query<TaskQueryParams>,
 ) -> Result<Json<TasksResponse>> {
+    user.require_scope(TokenScope::TasksRead)?;
+
     let page = params.page.unwrap_or(1).max(1);
     let per_page = params.per_page.unwrap_or(10).max(1);
     let offset = (page - 1) * per_page;
@@ -324,27 +1552,29 @@ pub async fn get_tasks(
     // --- SECCIÓN CORREGIDA Y SIMPLIFICADA ---
 
     // 1. Empezamos con la base de la consulta, que siempre es la misma.
-    let base_select = "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email";
+    let base_select = format!("SELECT {}", TASK_COLUMNS);
     let count_select = "SELECT COUNT(t.id)";
-    let from_clause = "FROM tasks t LEFT JOIN users u ON t.user_id = u.id";
 
     // 2. Construimos los builders
-    let mut query_builder = sqlx::QueryBuilder::new(format!("{} {}", base_select, from_clause));
-    let mut count_builder = sqlx::QueryBuilder::new(format!("{} {}", count_select, from_clause));
+    let mut query_builder = sqlx::QueryBuilder::new(format!("{} {}", base_select, TASK_FROM));
+    let mut count_builder = sqlx::QueryBuilder::new(format!("{} {}", count_select, TASK_FROM));
 
     // 3. Añadimos la condición del WHERE. Siempre empezamos con 'WHERE 1=1'
     //    para poder añadir 'AND' de forma segura.
     query_builder.push(" WHERE 1=1");
     count_builder.push(" WHERE 1=1");
 
-    // 4. Si el usuario NO es admin, añadimos la condición más importante.
-    if !user.is_admin() {
-        query_builder.push(" AND t.user_id = ").push_bind(user.user_id);
-        count_builder.push(" AND t.user_id = ").push_bind(user.user_id);
+    // 4. Si el rol NO permite ver todas las tareas, añadimos la condición más importante:
+    //    solo las tareas propias, las asignadas al usuario y las que sigue.
+    let can_read_all = user.has_permission(Permission::TasksReadAll);
+    if !can_read_all {
+        push_task_read_access(&mut query_builder, user.user_id);
+        push_task_read_access(&mut count_builder, user.user_id);
     }
 
-    // El resto del código no cambia.
-    apply_task_filters(&mut query_builder, &mut count_builder, &params, user.is_admin());
+    let assignees = resolve_user_filter(params.assignee.as_deref(), user.user_id, "assignee")?;
+    let watchers = resolve_user_filter(params.watcher.as_deref(), user.user_id, "watcher")?;
+    apply_task_filters(&mut query_builder, &mut count_builder, &params, &assignees, &watchers, can_read_all);
 
     let total_record: (i64,) = count_builder.build_query_as()
         .fetch_one(&state.db_pool)
@@ -359,7 +1589,7 @@ pub async fn get_tasks(
         "priority" => "t.priority",
         "status" => "t.status",
         "title" => "t.title",
-        "owner_name" if user.is_admin() => "u.name",
+        "owner_name" if can_read_all => "u.name",
         _ => "t.created_at",
     };
     let sort_direction = if sort_order.eq_ignore_ascii_case("asc") { "ASC" } else { "DESC" };
@@ -367,14 +1597,19 @@ pub async fn get_tasks(
     query_builder.push(format_args!(" ORDER BY {} {}", sort_column, sort_direction));
     query_builder.push(" LIMIT ").push_bind(per_page).push(" OFFSET ").push_bind(offset);
 
-    let tasks: Vec<Task> = query_builder.build_query_as()
+    let mut tasks: Vec<Task> = query_builder.build_query_as()
         .fetch_all(&state.db_pool)
         .await?;
+
+    if params.include_subtasks.unwrap_or(false) {
+        let visible_to = if can_read_all { None } else { Some(user.user_id) };
+        attach_subtasks(&state.db_pool, &mut tasks, visible_to).await?;
+    }
     
     let total_pages = if total == 0 { 0 } else { (total as f64 / per_page as f64).ceil() as i64 };
 
-    println!("->> HANDLER | Tareas obtenidas: {} (Usuario: {}, Admin: {})", 
-             tasks.len(), user.user_id, user.is_admin());
+    println!("->> HANDLER | Tareas obtenidas: {} (Usuario: {}, Todas: {})", 
+             tasks.len(), user.user_id, can_read_all);
 
     Ok(Json(TasksResponse {
         tasks,
@@ -389,7 +1624,9 @@ fn apply_task_filters<'a>(
     query_builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>,
     count_builder: &mut sqlx::QueryBuilder<'a, sqlx::Sqlite>,
     params: &'a TaskQueryParams,
-    is_admin: bool,
+    assignees: &[i32],
+    watchers: &[i32],
+    can_read_all: bool,
 ) {
     // 1. Filtro de BÚSQUEDA (search)
     if let Some(search_term) = &params.search {
@@ -440,27 +1677,18 @@ fn apply_task_filters<'a>(
         }
     }
     
-    // 4. Filtro por TAGS
+    // 4. Filtro por TAGS (coincidencia exacta con cualquiera de ellos)
     if let Some(tags) = &params.tags {
-        let tag_vec: Vec<String> = tags.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+        let tag_vec = parse_tags(&TagsInput::Csv(tags.clone()));
         if !tag_vec.is_empty() {
-            query_builder.push(" AND (");
-            for (i, tag) in tag_vec.iter().enumerate() {
-                if i > 0 {
-                    query_builder.push(" OR ");
-                }
-                query_builder.push("LOWER(t.tags) LIKE ").push_bind(format!("%{}%", tag.to_lowercase()));
-            }
-            query_builder.push(")");
-
-            count_builder.push(" AND (");
-            for (i, tag) in tag_vec.iter().enumerate() {
-                if i > 0 {
-                    count_builder.push(" OR ");
+            for builder in [&mut *query_builder, &mut *count_builder] {
+                builder.push(" AND EXISTS (SELECT 1 FROM task_tags tt JOIN tags tg ON tg.id = tt.tag_id WHERE tt.task_id = t.id AND tg.name IN (");
+                let mut separated = builder.separated(", ");
+                for tag in &tag_vec {
+                    separated.push_bind(tag.clone());
                 }
-                count_builder.push("LOWER(t.tags) LIKE ").push_bind(format!("%{}%", tag.to_lowercase()));
+                separated.push_unseparated("))");
             }
-            count_builder.push(")");
         }
     }
     
@@ -477,9 +1705,23 @@ fn apply_task_filters<'a>(
             count_builder.push(" AND t.due_date <= ").push_bind(end_date.clone());
         }
     }
+
+    // 6. Solo tareas de primer nivel (no subtareas)
+    if params.top_level.unwrap_or(false) {
+        query_builder.push(" AND t.parent_id IS NULL");
+        count_builder.push(" AND t.parent_id IS NULL");
+    }
+
+    // 7. Tareas bloqueadas por otras sin terminar (o sin bloqueos abiertos)
+    if let Some(blocked) = params.blocked {
+        let condition = if blocked { " AND EXISTS" } else { " AND NOT EXISTS" };
+        for builder in [&mut *query_builder, &mut *count_builder] {
+            builder.push(condition).push(" (SELECT 1 FROM task_dependencies d JOIN tasks b ON b.id = d.blocker_id WHERE d.task_id = t.id AND b.status != 'done')");
+        }
+    }
     
-    // --- FILTROS EXCLUSIVOS DE ADMINISTRADOR ---
-    if is_admin {
+    // --- FILTROS EXCLUSIVOS DE ROLES CON `tasks:read_all` ---
+    if can_read_all {
         if let Some(user_id) = params.user_id {
             query_builder.push(" AND t.user_id = ").push_bind(user_id);
             count_builder.push(" AND t.user_id = ").push_bind(user_id);
@@ -501,12 +1743,29 @@ fn apply_task_filters<'a>(
     }
     if let Some(assigned_to) = &params.assigned_to {
         if assigned_to == "unassigned" {
-            query_builder.push(" AND (t.assigned_to IS NULL OR t.assigned_to = '')");
-            count_builder.push(" AND (t.assigned_to IS NULL OR t.assigned_to = '')");
+            query_builder.push(" AND NOT EXISTS (SELECT 1 FROM task_assignees ta WHERE ta.task_id = t.id)");
+            count_builder.push(" AND NOT EXISTS (SELECT 1 FROM task_assignees ta WHERE ta.task_id = t.id)");
         } else if !assigned_to.is_empty() {
             let pattern = format!("%{}%", assigned_to.trim().to_lowercase());
-            query_builder.push(" AND LOWER(t.assigned_to) LIKE ").push_bind(pattern.clone());
-            count_builder.push(" AND LOWER(t.assigned_to) LIKE ").push_bind(pattern.clone());
+            for builder in [&mut *query_builder, &mut *count_builder] {
+                builder.push(" AND EXISTS (SELECT 1 FROM task_assignees ta JOIN users au ON au.id = ta.user_id WHERE ta.task_id = t.id AND LOWER(au.name) LIKE ")
+                    .push_bind(pattern.clone()).push(")");
+            }
+        }
+    }
+
+    // Filtros por personas: asignadas a alguno de los usuarios y seguidas por alguno de ellos
+    for (table, user_ids) in [("task_assignees", assignees), ("task_watchers", watchers)] {
+        if user_ids.is_empty() {
+            continue;
+        }
+        for builder in [&mut *query_builder, &mut *count_builder] {
+            builder.push(format_args!(" AND EXISTS (SELECT 1 FROM {} m WHERE m.task_id = t.id AND m.user_id IN (", table));
+            let mut separated = builder.separated(", ");
+            for user_id in user_ids {
+                separated.push_bind(*user_id);
+            }
+            separated.push_unseparated("))");
         }
     }
 }
@@ -524,36 +1783,44 @@ pub async fn get_task(
     user: AuthenticatedUserWithRole,
     Path(id): Path<i64>,
 ) -> Result<Json<Task>> {
-    let query = if user.is_admin() {
-        "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email 
-         FROM tasks t 
-         LEFT JOIN users u ON t.user_id = u.id 
-         WHERE t.id = ?"
-    } else {
-        "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email 
-         FROM tasks t 
-         LEFT JOIN users u ON t.user_id = u.id 
-         WHERE t.id = ? AND t.user_id = ?"
-    };
+    user.require_scope(TokenScope::TasksRead)?;
+
+    let mut conn = state.db_pool.acquire().await?;
+    find_accessible_task(&mut conn, &user, id, Permission::TasksReadAll).await.map(Json)
+}
 
-    let task = if user.is_admin() {
-        sqlx::query_as::<_, Task>(query)
-            .bind(id)
-            .fetch_optional(&state.db_pool)
-            .await?
-    } else {
-        sqlx::query_as::<_, Task>(query)
-            .bind(id)
-            .bind(user.user_id)
-            .fetch_optional(&state.db_pool)
-            .await?
-    };
+/// Busca una tarea a la que el usuario tiene acceso: cualquiera si su rol concede `permission`,
+/// si no, solo las suyas y las que tiene asignadas. Para consultarla (`TasksReadAll`) también
+/// sirven las que sigue.
+async fn find_accessible_task(
+    conn: &mut sqlx::SqliteConnection,
+    user: &AuthenticatedUserWithRole,
+    id: i64,
+    permission: Permission,
+) -> Result<Task> {
+    if user.has_permission(permission) {
+        return fetch_task(conn, id).await;
+    }
+
+    let read_only = permission == Permission::TasksReadAll;
+    let access = if read_only { TASK_READ_ACCESS } else { TASK_ACCESS };
+    let sql = format!("SELECT {} {} WHERE t.id = ? AND {}", TASK_COLUMNS, TASK_FROM, access);
+    let mut query = sqlx::query_as::<_, Task>(&sql)
+        .bind(id)
+        .bind(user.user_id)
+        .bind(user.user_id);
+    if read_only {
+        query = query.bind(user.user_id);
+    }
 
-    task.ok_or_else(|| AppError::NotFound(format!("Tarea con ID {} no encontrada", id)))
-        .map(Json)
+    query
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Tarea con ID {} no encontrada", id)))
 }
 
-/// Actualiza una tarea existente.
+/// Actualiza una tarea existente. La persona asignada puede editarla igual que su dueño.
+/// Completar la última tarea de una serie repetitiva crea la siguiente.
 #[utoipa::path(put, path = "/tasks/{id}", tag = "Tasks", security(("bearer_auth" = [])), request_body = UpdateTaskRequest)]
 pub async fn update_task(
     State(state): State<AppState>,
@@ -561,6 +1828,8 @@ pub async fn update_task(
     Path(id): Path<i64>,
     Json(payload): Json<UpdateTaskRequest>,
 ) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
     payload.validate()?;
 
     if !state.config.allow_past_due_dates {
@@ -578,57 +1847,57 @@ pub async fn update_task(
     let mut tx = state.db_pool.begin().await?;
 
     // Verificar permisos
-    let query = if user.is_admin() {
-        "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email FROM tasks t LEFT JOIN users u ON t.user_id = u.id WHERE t.id = ?"
-    } else {
-        "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email FROM tasks t LEFT JOIN users u ON t.user_id = u.id WHERE t.id = ? AND t.user_id = ?"
-    };
-
-    let task: Task = if user.is_admin() {
-        sqlx::query_as(query)
-            .bind(id)
-            .fetch_optional(&mut *tx)
-            .await?
-    } else {
-        sqlx::query_as(query)
-            .bind(id)
-            .bind(user.user_id)
-            .fetch_optional(&mut *tx)
-            .await?
-    }
-    .ok_or_else(|| AppError::NotFound(format!("Tarea con ID {} no encontrada", id)))?;
+    let task = find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
 
     let title = payload.title.unwrap_or(task.title);
     let description = payload.description;
-    let status = payload.status.unwrap_or(task.status);
+    let status = payload.status.unwrap_or(task.status.clone());
     let priority = payload.priority.unwrap_or(task.priority);
     let due_date = payload.due_date;
-    let tags = payload.tags;
-    let assigned_to = payload.assigned_to;
+    let tags = payload.tags.as_ref().map(parse_tags).unwrap_or_default();
+    let completed = status == "done" && task.status != "done";
+    // La siguiente repetición se calcula desde el vencimiento de esta, aunque la petición lo borre
+    let occurrence_due_date = due_date.clone().or(task.due_date);
+
+    if completed && state.config.block_done_with_open_dependencies {
+        let blockers = open_blockers(&mut tx, id).await?;
+        if !blockers.is_empty() {
+            let ids: Vec<String> = blockers.iter().map(|blocker| blocker.to_string()).collect();
+            return Err(AppError::Conflict(format!(
+                "La tarea sigue bloqueada por las tareas abiertas: {}",
+                ids.join(", ")
+            )));
+        }
+    }
 
     sqlx::query(
         "UPDATE tasks SET title = ?, description = ?, status = ?, priority = ?, 
-         due_date = ?, tags = ?, assigned_to = ?, updated_at = ? WHERE id = ?"
+         due_date = ?, updated_at = ? WHERE id = ?"
     )
         .bind(title).bind(description).bind(status).bind(priority)
-        .bind(due_date).bind(tags).bind(assigned_to).bind(Utc::now().to_rfc3339()).bind(id)
+        .bind(due_date).bind(Utc::now().to_rfc3339()).bind(id)
         .execute(&mut *tx)
         .await?;
 
-    let updated_task: Task = sqlx::query_as(
-        "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email
-         FROM tasks t
-         LEFT JOIN users u ON t.user_id = u.id
-         WHERE t.id = ?"
-    )
-        .bind(id)
-        .fetch_one(&mut *tx)
-        .await?;
+    // Los tags se guardan en el catálogo del dueño de la tarea, aunque la edite otro usuario
+    set_task_tags(&mut tx, id, task.user_id, &tags).await?;
+    // Sin `assignees` se conservan las personas asignadas; se gestionan también por separado
+    if let Some(assignees) = &payload.assignees {
+        set_task_assignees(&mut tx, id, assignees).await?;
+    }
+
+    if completed {
+        if let Some(next_id) = spawn_next_occurrence(&mut tx, id, occurrence_due_date.as_deref()).await? {
+            println!("->> HANDLER | Siguiente repetición de la tarea (ID: {}) creada: (ID: {})", id, next_id);
+        }
+    }
+
+    let updated_task = fetch_task(&mut tx, id).await?;
 
     tx.commit().await?;
     
-    println!("->> HANDLER | Tarea actualizada: (ID: {}) por usuario (ID: {}, Admin: {})", 
-             id, user.user_id, user.is_admin());
+    println!("->> HANDLER | Tarea actualizada: (ID: {}) por usuario (ID: {}, Role: {})", 
+             id, user.user_id, user.role);
     Ok(Json(updated_task))
 }
 
@@ -639,13 +1908,16 @@ pub async fn delete_task(
     user: AuthenticatedUserWithRole,
     Path(id): Path<i64>,
 ) -> Result<StatusCode> {
-    let query = if user.is_admin() {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let can_delete_all = user.has_permission(Permission::TasksDeleteAll);
+    let query = if can_delete_all {
         "DELETE FROM tasks WHERE id = ?"
     } else {
         "DELETE FROM tasks WHERE id = ? AND user_id = ?"
     };
 
-    let result = if user.is_admin() {
+    let result = if can_delete_all {
         sqlx::query(query)
             .bind(id)
             .execute(&state.db_pool)
@@ -661,9 +1933,464 @@ pub async fn delete_task(
     if result.rows_affected() == 0 {
         return Err(AppError::NotFound(format!("Tarea con ID {} no encontrada", id)));
     }
+    prune_unused_tags(&mut *state.db_pool.acquire().await?).await?;
     
-    println!("->> HANDLER | Tarea eliminada: (ID: {}) por usuario (ID: {}, Admin: {})", 
-             id, user.user_id, user.is_admin());
+    println!("->> HANDLER | Tarea eliminada: (ID: {}) por usuario (ID: {}, Role: {})", 
+             id, user.user_id, user.role);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Handlers de Personas Asignadas y Seguidores ---
+
+/// Asigna la tarea a un usuario más. Requiere poder editar la tarea.
+#[utoipa::path(
+    post,
+    path = "/tasks/{id}/assignees",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea")),
+    request_body = AddAssigneeRequest,
+    responses((status = 200, body = Task))
+)]
+pub async fn add_assignee(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+    Json(payload): Json<AddAssigneeRequest>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    add_task_assignee(&mut tx, id, payload.user_id).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Usuario (ID: {}) asignado a la tarea (ID: {}) por usuario (ID: {})", payload.user_id, id, user.user_id);
+    Ok(Json(task))
+}
+
+/// Quita a un usuario de las personas asignadas. Requiere poder editar la tarea.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/assignees/{user_id}",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(
+        ("id" = i64, Path, description = "ID de la tarea"),
+        ("user_id" = i32, Path, description = "ID del usuario asignado")
+    ),
+    responses((status = 200, body = Task))
+)]
+pub async fn remove_assignee(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path((id, assignee_id)): Path<(i64, i32)>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let task = find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    if !task.assignees.iter().any(|member| member.id == assignee_id) {
+        return Err(AppError::NotFound(format!("El usuario con ID {} no está asignado a la tarea", assignee_id)));
+    }
+    remove_task_assignee(&mut tx, id, assignee_id).await?;
+    // Quien se quita a sí mismo puede perder el acceso, pero recibe la tarea actualizada
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Usuario (ID: {}) desasignado de la tarea (ID: {}) por usuario (ID: {})", assignee_id, id, user.user_id);
+    Ok(Json(task))
+}
+
+/// Hace que otro usuario siga la tarea, por ejemplo para que la revise; seguirla le da acceso
+/// de lectura. Requiere poder editar la tarea.
+#[utoipa::path(
+    post,
+    path = "/tasks/{id}/watchers",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea")),
+    request_body = AddWatcherRequest,
+    responses((status = 200, body = Task))
+)]
+pub async fn add_watcher(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+    Json(payload): Json<AddWatcherRequest>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    add_task_watcher(&mut tx, id, payload.user_id).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Usuario (ID: {}) sigue la tarea (ID: {}) por usuario (ID: {})", payload.user_id, id, user.user_id);
+    Ok(Json(task))
+}
+
+/// Hace que un usuario deje de seguir la tarea. Requiere poder editar la tarea.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/watchers/{user_id}",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(
+        ("id" = i64, Path, description = "ID de la tarea"),
+        ("user_id" = i32, Path, description = "ID del usuario que la sigue")
+    ),
+    responses((status = 200, body = Task))
+)]
+pub async fn remove_watcher(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path((id, watcher_id)): Path<(i64, i32)>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let task = find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    if !task.watchers.iter().any(|member| member.id == watcher_id) {
+        return Err(AppError::NotFound(format!("El usuario con ID {} no sigue la tarea", watcher_id)));
+    }
+    set_watching(&mut tx, id, watcher_id, false).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Usuario (ID: {}) deja de seguir la tarea (ID: {}) por usuario (ID: {})", watcher_id, id, user.user_id);
+    Ok(Json(task))
+}
+
+/// Sigue una tarea a la que el usuario tiene acceso.
+#[utoipa::path(
+    post,
+    path = "/tasks/{id}/watch",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea")),
+    responses((status = 200, body = Task))
+)]
+pub async fn watch_task(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+) -> Result<Json<Task>> {
+    toggle_watch(&state, &user, id, true).await.map(Json)
+}
+
+/// Deja de seguir una tarea.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/watch",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea")),
+    responses((status = 200, body = Task))
+)]
+pub async fn unwatch_task(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+) -> Result<Json<Task>> {
+    toggle_watch(&state, &user, id, false).await.map(Json)
+}
+
+async fn toggle_watch(state: &AppState, user: &AuthenticatedUserWithRole, id: i64, watching: bool) -> Result<Task> {
+    user.require_scope(TokenScope::TasksRead)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, user, id, Permission::TasksReadAll).await?;
+    set_watching(&mut tx, id, user.user_id, watching).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Usuario (ID: {}) {} la tarea (ID: {})", user.user_id, if watching { "sigue" } else { "deja de seguir" }, id);
+    Ok(task)
+}
+
+// --- Handlers de Dependencias ---
+
+/// Bloquea una tarea hasta que otra se termine. Requiere poder editar la tarea y ver la que la bloquea;
+/// los enlaces que formarían un ciclo se rechazan.
+#[utoipa::path(
+    post,
+    path = "/tasks/{id}/dependencies",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea bloqueada")),
+    request_body = AddDependencyRequest,
+    responses((status = 200, body = Task))
+)]
+pub async fn add_task_dependency(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+    Json(payload): Json<AddDependencyRequest>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    find_accessible_task(&mut tx, &user, payload.blocker_id, Permission::TasksReadAll).await?;
+    add_dependency(&mut tx, id, payload.blocker_id).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Tarea (ID: {}) bloqueada por la tarea (ID: {}) por usuario (ID: {})", id, payload.blocker_id, user.user_id);
+    Ok(Json(task))
+}
+
+/// Elimina el bloqueo de una tarea por otra.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/dependencies/{blocker_id}",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(
+        ("id" = i64, Path, description = "ID de la tarea bloqueada"),
+        ("blocker_id" = i64, Path, description = "ID de la tarea que la bloquea")
+    ),
+    responses((status = 200, body = Task))
+)]
+pub async fn remove_task_dependency(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path((id, blocker_id)): Path<(i64, i64)>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    remove_dependency(&mut tx, id, blocker_id).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Tarea (ID: {}) desbloqueada de la tarea (ID: {}) por usuario (ID: {})", id, blocker_id, user.user_id);
+    Ok(Json(task))
+}
+
+// --- Handlers de Tareas Repetitivas ---
+
+/// Hace que una tarea se repita o cambia la regla de su serie. Requiere poder editar la tarea y que
+/// tenga fecha de vencimiento; la serie vuelve a activarse y COUNT cuenta desde esta tarea.
+#[utoipa::path(
+    put,
+    path = "/tasks/{id}/recurrence",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea")),
+    request_body = RecurrenceInput,
+    responses((status = 200, body = Task))
+)]
+pub async fn set_task_recurrence(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+    Json(payload): Json<RecurrenceInput>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let rule = RecurrenceRule::from_input(&payload)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    let task = find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    set_recurrence(&mut tx, &task, &rule).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Tarea (ID: {}) repetida con la regla '{}' por usuario (ID: {})", id, rule.to_rrule(), user.user_id);
+    Ok(Json(task))
+}
+
+/// Detiene la serie de una tarea repetitiva. Las tareas ya creadas se conservan.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/recurrence",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea")),
+    responses((status = 200, body = Task))
+)]
+pub async fn stop_task_recurrence(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+) -> Result<Json<Task>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    stop_recurrence(&mut tx, id).await?;
+    let task = fetch_task(&mut tx, id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Serie de la tarea (ID: {}) detenida por usuario (ID: {})", id, user.user_id);
+    Ok(Json(task))
+}
+
+// --- Handlers de Listas de Comprobación ---
+
+/// Añade un elemento a la lista de comprobación de una tarea. Requiere poder editar la tarea.
+#[utoipa::path(
+    post,
+    path = "/tasks/{id}/checklist",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID de la tarea")),
+    request_body = CreateChecklistItemRequest,
+    responses((status = 201, body = ChecklistItem))
+)]
+pub async fn create_checklist_item(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path(id): Path<i64>,
+    Json(payload): Json<CreateChecklistItemRequest>,
+) -> Result<(StatusCode, Json<ChecklistItem>)> {
+    user.require_scope(TokenScope::TasksWrite)?;
+    payload.validate()?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    let item = add_checklist_item(&mut tx, id, &payload.title).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Elemento de lista (ID: {}) añadido a la tarea (ID: {}) por usuario (ID: {})", item.id, id, user.user_id);
+    Ok((StatusCode::CREATED, Json(item)))
+}
+
+/// Marca, desmarca o renombra un elemento de la lista de comprobación.
+#[utoipa::path(
+    put,
+    path = "/tasks/{id}/checklist/{item_id}",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(
+        ("id" = i64, Path, description = "ID de la tarea"),
+        ("item_id" = i64, Path, description = "ID del elemento")
+    ),
+    request_body = UpdateChecklistItemRequest,
+    responses((status = 200, body = ChecklistItem))
+)]
+pub async fn update_checklist_item_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path((id, item_id)): Path<(i64, i64)>,
+    Json(payload): Json<UpdateChecklistItemRequest>,
+) -> Result<Json<ChecklistItem>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+    payload.validate()?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    let item = update_checklist_item(&mut tx, id, item_id, &payload).await?;
+    tx.commit().await?;
+
+    Ok(Json(item))
+}
+
+/// Elimina un elemento de la lista de comprobación.
+#[utoipa::path(
+    delete,
+    path = "/tasks/{id}/checklist/{item_id}",
+    tag = "Tasks",
+    security(("bearer_auth" = [])),
+    params(
+        ("id" = i64, Path, description = "ID de la tarea"),
+        ("item_id" = i64, Path, description = "ID del elemento")
+    )
+)]
+pub async fn delete_checklist_item_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUserWithRole,
+    Path((id, item_id)): Path<(i64, i64)>,
+) -> Result<StatusCode> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let mut tx = state.db_pool.begin().await?;
+    find_accessible_task(&mut tx, &user, id, Permission::TasksUpdateAll).await?;
+    delete_checklist_item(&mut tx, id, item_id).await?;
+    tx.commit().await?;
+
+    println!("->> HANDLER | Elemento de lista (ID: {}) eliminado de la tarea (ID: {}) por usuario (ID: {})", item_id, id, user.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Handlers de Tags ---
+
+/// Lista los tags del usuario con el número de tareas que usan cada uno.
+#[utoipa::path(get, path = "/tags", tag = "Tags", security(("bearer_auth" = [])), responses((status = 200, body = [Tag])))]
+pub async fn get_tags(State(state): State<AppState>, user: AuthenticatedUser) -> Result<Json<Vec<Tag>>> {
+    user.require_scope(TokenScope::TasksRead)?;
+    Ok(Json(list_tags(&state.db_pool, user.user_id).await?))
+}
+
+/// Cambia el nombre de un tag en todas las tareas del usuario.
+#[utoipa::path(
+    put,
+    path = "/tags/{id}",
+    tag = "Tags",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID del tag")),
+    request_body = RenameTagRequest,
+    responses((status = 200, body = Tag))
+)]
+pub async fn rename_tag_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(tag_id): Path<i64>,
+    Json(payload): Json<RenameTagRequest>,
+) -> Result<Json<Tag>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+    payload.validate()?;
+
+    let tag = rename_tag(&state.db_pool, user.user_id, tag_id, &payload.name).await?;
+    println!("->> HANDLER | Tag renombrado: (ID: {}) a '{}' por usuario (ID: {})", tag.id, tag.name, user.user_id);
+    Ok(Json(tag))
+}
+
+/// Fusiona un tag en otro: las tareas pasan a usar el tag de destino.
+#[utoipa::path(
+    post,
+    path = "/tags/{id}/merge",
+    tag = "Tags",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID del tag que desaparece")),
+    request_body = MergeTagsRequest,
+    responses((status = 200, body = Tag))
+)]
+pub async fn merge_tags_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(tag_id): Path<i64>,
+    Json(payload): Json<MergeTagsRequest>,
+) -> Result<Json<Tag>> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    let tag = merge_tags(&state.db_pool, user.user_id, tag_id, payload.target_id).await?;
+    println!("->> HANDLER | Tag (ID: {}) fusionado en '{}' por usuario (ID: {})", tag_id, tag.name, user.user_id);
+    Ok(Json(tag))
+}
+
+/// Elimina un tag y lo quita de todas las tareas del usuario.
+#[utoipa::path(
+    delete,
+    path = "/tags/{id}",
+    tag = "Tags",
+    security(("bearer_auth" = [])),
+    params(("id" = i64, Path, description = "ID del tag"))
+)]
+pub async fn delete_tag_handler(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(tag_id): Path<i64>,
+) -> Result<StatusCode> {
+    user.require_scope(TokenScope::TasksWrite)?;
+
+    delete_tag(&state.db_pool, user.user_id, tag_id).await?;
+    println!("->> HANDLER | Tag eliminado: (ID: {}) por usuario (ID: {})", tag_id, user.user_id);
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -673,7 +2400,7 @@ pub async fn delete_task(
 #[utoipa::path(get, path = "/admin/users", tag = "Admin", security(("bearer_auth" = [])))]
 pub async fn get_all_users(
     State(state): State<AppState>,
-    _admin: AdminUser,
+    _admin: Authorized<ReadUsers>,
     Query(params): Query<TaskQueryParams>, // Reutilizamos para paginación
 ) -> Result<Json<UsersResponse>> {
     let page = params.page.unwrap_or(1).max(1);
@@ -685,7 +2412,7 @@ pub async fn get_all_users(
         .await?;
 
     let users: Vec<UserSummary> = sqlx::query_as(
-        "SELECT u.id, u.name, u.email, u.role, u.created_at,
+        "SELECT u.id, u.name, u.email, u.role, u.status, u.created_at,
          COUNT(t.id) as task_count
          FROM users u
          LEFT JOIN tasks t ON u.id = t.user_id
@@ -721,7 +2448,7 @@ pub async fn get_all_users(
 )]
 pub async fn get_user_tasks(
     State(state): State<AppState>,
-    _admin: AdminUser,
+    _admin: Authorized<ReadAllTasks>,
     Path(user_id): Path<i32>,
     Query(params): Query<TaskQueryParams>,
 ) -> Result<Json<TasksResponse>> {
@@ -744,14 +2471,10 @@ pub async fn get_user_tasks(
         .fetch_one(&state.db_pool)
         .await?;
 
-    let tasks: Vec<Task> = sqlx::query_as(
-        "SELECT t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, t.tags, t.assigned_to, u.name as owner_name, u.email as owner_email
-         FROM tasks t
-         LEFT JOIN users u ON t.user_id = u.id
-         WHERE t.user_id = ?
-         ORDER BY t.created_at DESC
-         LIMIT ? OFFSET ?"
-    )
+    let tasks: Vec<Task> = sqlx::query_as(&format!(
+        "SELECT {} {} WHERE t.user_id = ? ORDER BY t.created_at DESC LIMIT ? OFFSET ?",
+        TASK_COLUMNS, TASK_FROM
+    ))
         .bind(user_id)
         .bind(per_page)
         .bind(offset)
@@ -778,7 +2501,7 @@ pub async fn get_user_tasks(
 #[utoipa::path(get, path = "/admin/stats", tag = "Admin", security(("bearer_auth" = [])))]
 pub async fn get_system_stats(
     State(state): State<AppState>,
-    _admin: AdminUser,
+    _admin: Authorized<ReadStats>,
 ) -> Result<Json<SystemStats>> {
     
     // --- PASO 1: Obtener las estadísticas que no dependen de la tabla 'tasks' ---
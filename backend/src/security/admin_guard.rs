@@ -4,46 +4,46 @@ use axum::{
     http::request::Parts,  
 };  
 use crate::{  
+    auth::personal_tokens::{check_scope, check_session, TokenScope},
+    auth::middleware::check_not_impersonated,
     auth::AuthenticatedUser,  
     error::{AppError, Result},  
+    security::permissions::{cached_role_permissions, Permission},
     AppState,  
 };  
   
-#[derive(Debug, Clone, PartialEq)]  
-pub enum UserRole {  
-    User,  
-    Admin,  
-}  
-  
-impl UserRole {  
-    pub fn from_string(role: &str) -> Self {  
-        match role.to_lowercase().as_str() {  
-            "admin" => UserRole::Admin,  
-            _ => UserRole::User,  
-        }  
-    }  
-      
-    pub fn to_string(&self) -> &'static str {  
-        match self {  
-            UserRole::User => "user",  
-            UserRole::Admin => "admin",  
-        }  
-    }  
-}  
-  
 /// Representa un usuario autenticado con información de rol  
 #[derive(Debug)]  
 pub struct AuthenticatedUserWithRole {  
     pub user_id: i32,  
-    pub role: UserRole,  
-    pub email: String,  
-    pub name: String,  
+    pub role: String,  
+    /// Permisos concedidos por el rol (sin filtrar por los scopes del token).
+    pub permissions: Vec<Permission>,
+    /// Scopes del token personal usado (`None` para sesiones con JWT).
+    pub scopes: Option<Vec<TokenScope>>,
+    /// Administrador que suplanta al usuario, si lo hay.
+    pub impersonator_id: Option<i32>,
 }  
   
 impl AuthenticatedUserWithRole {  
-    pub fn is_admin(&self) -> bool {  
-        self.role == UserRole::Admin  
+    /// Un token personal solo concede permisos del rol si incluye el scope `admin`.
+    pub fn has_permission(&self, permission: Permission) -> bool {  
+        self.permissions.contains(&permission) && check_scope(&self.scopes, TokenScope::Admin).is_ok()
     }  
+
+    /// Indica si el rol concede algún permiso más allá de las tareas propias.
+    pub fn is_privileged(&self) -> bool {
+        !self.permissions.is_empty()
+    }
+
+    pub fn require_scope(&self, scope: TokenScope) -> Result<()> {
+        check_scope(&self.scopes, scope)
+    }
+
+    pub fn require_session(&self) -> Result<()> {
+        check_session(&self.scopes)?;
+        check_not_impersonated(self.impersonator_id)
+    }
 }  
   
 #[async_trait]  
@@ -59,69 +59,39 @@ impl FromRequestParts<AppState> for AuthenticatedUserWithRole {
         // Primero obtener el usuario autenticado básico  
         let auth_user = AuthenticatedUser::from_request_parts(parts, state).await?;  
           
-        // Luego obtener información completa del usuario incluyendo el rol  
-        let user_data: UserWithRole = sqlx::query_as(  
-            "SELECT id, name, email, role FROM users WHERE id = ?"  
-        )  
-        .bind(auth_user.user_id)  
-        .fetch_optional(&state.db_pool)  
-        .await?  
-        .ok_or_else(|| {  
-            AppError::Authentication("Usuario no encontrado en la base de datos".to_string())  
-        })?;  
-  
-        let role = UserRole::from_string(&user_data.role);  
+        // El rol viene del token y sus permisos de la caché: no se consulta la base de datos
+        let permissions = cached_role_permissions(state, &auth_user.role).await?;
+
+        // Política REQUIRE_ADMIN_2FA: un usuario con permisos sin 2FA solo puede usar las
+        // rutas de `AuthenticatedUser` (entre ellas, las de alta de 2FA).
+        if !permissions.is_empty() && state.config.require_admin_2fa && !auth_user.two_factor_enabled
+            && !two_factor_enabled_now(state, auth_user.user_id).await?
+        {
+            println!("->> MIDDLEWARE | Acceso denegado: administrador sin 2FA (ID: {})", auth_user.user_id);
+            return Err(AppError::Authentication(
+                "Los administradores deben activar la autenticación en dos pasos".to_string()
+            ));
+        }
           
         println!("->> MIDDLEWARE | Usuario autenticado (ID: {}, Role: {})",   
-                 auth_user.user_id, role.to_string());  
+                 auth_user.user_id, auth_user.role);  
   
         Ok(AuthenticatedUserWithRole {  
             user_id: auth_user.user_id,  
-            role,  
-            email: user_data.email,  
-            name: user_data.name,  
+            role: auth_user.role,  
+            permissions,
+            scopes: auth_user.scopes,
+            impersonator_id: auth_user.impersonator_id,
         })  
     }  
 }  
   
-#[allow(dead_code)] 
-#[derive(Debug)]
-pub struct AdminUser {
-    pub email: String,
-    pub name: String,
+// El token puede ser anterior a la activación del 2FA; solo entonces se consulta la base de datos.
+async fn two_factor_enabled_now(state: &AppState, user_id: i32) -> Result<bool> {
+    let totp_enabled_at: Option<String> = sqlx::query_scalar("SELECT totp_enabled_at FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db_pool)
+        .await?
+        .flatten();
+    Ok(totp_enabled_at.is_some())
 }
-  
-#[async_trait]  
-impl FromRequestParts<AppState> for AdminUser {  
-    type Rejection = AppError;  
-  
-    async fn from_request_parts(  
-        parts: &mut Parts,  
-        state: &AppState,  
-    ) -> Result<Self> {  
-        println!("->> MIDDLEWARE | Verificando acceso de administrador...");  
-          
-        let auth_user = AuthenticatedUserWithRole::from_request_parts(parts, state).await?;  
-          
-        if !auth_user.is_admin() {  
-            println!("->> MIDDLEWARE | Acceso denegado: usuario no es administrador");  
-            return Err(AppError::Authentication(  
-                "Se requieren privilegios de administrador para acceder a este recurso".to_string()  
-            ));  
-        }  
-          
-        println!("->> MIDDLEWARE | Acceso de administrador concedido (ID: {})", auth_user.user_id);  
-          
-        Ok(AdminUser {  
-            email: auth_user.email,  
-            name: auth_user.name,  
-        })  
-    }  
-}  
-  
-#[derive(sqlx::FromRow, Debug)]  
-struct UserWithRole {  
-    name: String,  
-    email: String,  
-    role: String,  
-}  
\ No newline at end of file
@@ -0,0 +1,68 @@
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::{
+    error::Result,
+    models::{AuditLogEntry, AuditLogQueryParams},
+};
+
+/// Acción registrada al emitir un token de suplantación.
+pub const IMPERSONATION_STARTED: &str = "impersonation.started";
+/// Acción registrada por cada petición hecha con un token de suplantación.
+pub const IMPERSONATED_REQUEST: &str = "impersonation.request";
+
+/// Datos de la petición que origina una entrada de auditoría.
+#[derive(Debug, Default)]
+pub struct AuditContext<'a> {
+    pub method: Option<&'a str>,
+    pub path: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+}
+
+/// Añade una entrada al registro de auditoría: `actor_id` actúa sobre (o como) `user_id`.
+pub async fn record_audit_event(
+    db_pool: &SqlitePool,
+    actor_id: i32,
+    user_id: i32,
+    action: &str,
+    context: AuditContext<'_>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO audit_log (actor_id, user_id, action, method, path, ip_address, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(actor_id)
+    .bind(user_id)
+    .bind(action)
+    .bind(context.method)
+    .bind(context.path)
+    .bind(context.ip_address)
+    .bind(Utc::now().to_rfc3339())
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Consulta el registro de auditoría, de la entrada más reciente a la más antigua.
+pub async fn list_audit_log(db_pool: &SqlitePool, params: &AuditLogQueryParams) -> Result<Vec<AuditLogEntry>> {
+    let mut query_builder = sqlx::QueryBuilder::new(
+        "SELECT id, actor_id, user_id, action, method, path, ip_address, created_at FROM audit_log WHERE 1=1"
+    );
+
+    if let Some(actor_id) = params.actor_id {
+        query_builder.push(" AND actor_id = ").push_bind(actor_id);
+    }
+    if let Some(user_id) = params.user_id {
+        query_builder.push(" AND user_id = ").push_bind(user_id);
+    }
+    if let Some(action) = &params.action {
+        query_builder.push(" AND action = ").push_bind(action.clone());
+    }
+
+    let limit = params.limit.unwrap_or(100).clamp(1, 500);
+    query_builder.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+
+    let entries = query_builder.build_query_as().fetch_all(db_pool).await?;
+    Ok(entries)
+}
@@ -1,5 +1,9 @@
 pub mod rate_limiter;
 pub mod admin_guard;
+pub mod audit;
+pub mod permissions;
+pub mod roles;
 
-pub use rate_limiter::{get_real_ip, record_login_attempt, rate_limit_middleware};
-pub use admin_guard::{AdminUser, AuthenticatedUserWithRole};
+pub use rate_limiter::{check_account_lockout, clear_failed_attempts, get_real_ip, record_login_attempt, rate_limit_middleware};
+pub use admin_guard::AuthenticatedUserWithRole;
+pub use permissions::{Authorized, Permission};
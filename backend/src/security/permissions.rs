@@ -0,0 +1,170 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+};
+use sqlx::SqlitePool;
+
+use crate::{
+    error::{AppError, Result},
+    security::admin_guard::AuthenticatedUserWithRole,
+    AppState,
+};
+
+/// Permisos granulares que un rol puede conceder. Sin permisos, un usuario solo
+/// gestiona sus propias tareas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    TasksReadAll,
+    TasksUpdateAll,
+    TasksDeleteAll,
+    UsersRead,
+    UsersManage,
+    UsersImpersonate,
+    InvitesManage,
+    RolesManage,
+    StatsRead,
+    AuditRead,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 10] = [
+        Permission::TasksReadAll,
+        Permission::TasksUpdateAll,
+        Permission::TasksDeleteAll,
+        Permission::UsersRead,
+        Permission::UsersManage,
+        Permission::UsersImpersonate,
+        Permission::InvitesManage,
+        Permission::RolesManage,
+        Permission::StatsRead,
+        Permission::AuditRead,
+    ];
+
+    pub fn from_string(permission: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.as_str() == permission)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::TasksReadAll => "tasks:read_all",
+            Permission::TasksUpdateAll => "tasks:update_all",
+            Permission::TasksDeleteAll => "tasks:delete_all",
+            Permission::UsersRead => "users:read",
+            Permission::UsersManage => "users:manage",
+            Permission::UsersImpersonate => "users:impersonate",
+            Permission::InvitesManage => "invites:manage",
+            Permission::RolesManage => "roles:manage",
+            Permission::StatsRead => "stats:read",
+            Permission::AuditRead => "audit:read",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Permission::TasksReadAll => "Consultar las tareas de todos los usuarios",
+            Permission::TasksUpdateAll => "Editar las tareas de todos los usuarios",
+            Permission::TasksDeleteAll => "Eliminar las tareas de todos los usuarios",
+            Permission::UsersRead => "Listar los usuarios del sistema",
+            Permission::UsersManage => "Cambiar roles, desbloquear y eliminar cuentas",
+            Permission::UsersImpersonate => "Actuar temporalmente como otro usuario",
+            Permission::InvitesManage => "Gestionar invitaciones y el modo de registro",
+            Permission::RolesManage => "Crear, modificar y eliminar roles",
+            Permission::StatsRead => "Consultar las estadísticas del sistema",
+            Permission::AuditRead => "Consultar el registro de auditoría",
+        }
+    }
+}
+
+/// Carga los permisos concedidos a un rol. Los valores desconocidos se ignoran.
+pub async fn load_role_permissions(db_pool: &SqlitePool, role: &str) -> Result<Vec<Permission>> {
+    let permissions: Vec<String> = sqlx::query_scalar("SELECT permission FROM role_permissions WHERE role = ?")
+        .bind(role)
+        .fetch_all(db_pool)
+        .await?;
+
+    Ok(permissions.iter().filter_map(|p| Permission::from_string(p)).collect())
+}
+
+/// Permisos de un rol a través de la caché del `AppState`; los cambios en los roles la vacían.
+pub async fn cached_role_permissions(state: &AppState, role: &str) -> Result<Vec<Permission>> {
+    if let Some(permissions) = state.role_permissions.get(&role.to_string()) {
+        return Ok(permissions);
+    }
+
+    let permissions = load_role_permissions(&state.db_pool, role).await?;
+    state.role_permissions.insert(role.to_string(), permissions.clone());
+    Ok(permissions)
+}
+
+/// Permiso exigido por un extractor `Authorized<P>`.
+pub trait RequiredPermission {
+    const PERMISSION: Permission;
+}
+
+macro_rules! required_permissions {
+    ($($marker:ident => $permission:ident),* $(,)?) => {
+        $(
+            #[derive(Debug)]
+            pub enum $marker {}
+
+            impl RequiredPermission for $marker {
+                const PERMISSION: Permission = Permission::$permission;
+            }
+        )*
+    };
+}
+
+required_permissions! {
+    ReadAllTasks => TasksReadAll,
+    ReadUsers => UsersRead,
+    ManageUsers => UsersManage,
+    ImpersonateUsers => UsersImpersonate,
+    ManageInvites => InvitesManage,
+    ManageRoles => RolesManage,
+    ReadStats => StatsRead,
+    ReadAuditLog => AuditRead,
+}
+
+/// Usuario autenticado cuyo rol concede el permiso `P`; sustituye a las
+/// comprobaciones de "es admin" en las rutas de administración.
+#[derive(Debug)]
+pub struct Authorized<P: RequiredPermission> {
+    pub user: AuthenticatedUserWithRole,
+    _permission: PhantomData<P>,
+}
+
+impl<P: RequiredPermission> Deref for Authorized<P> {
+    type Target = AuthenticatedUserWithRole;
+
+    fn deref(&self) -> &Self::Target {
+        &self.user
+    }
+}
+
+#[async_trait]
+impl<P: RequiredPermission + Send> FromRequestParts<AppState> for Authorized<P> {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self> {
+        let user = AuthenticatedUserWithRole::from_request_parts(parts, state).await?;
+
+        if !user.has_permission(P::PERMISSION) {
+            println!("->> MIDDLEWARE | Acceso denegado: falta el permiso '{}' (ID: {})", P::PERMISSION.as_str(), user.user_id);
+            return Err(AppError::Authentication(format!(
+                "Se requiere el permiso '{}' para acceder a este recurso",
+                P::PERMISSION.as_str()
+            )));
+        }
+
+        println!("->> MIDDLEWARE | Permiso '{}' concedido al rol '{}' (ID: {})", P::PERMISSION.as_str(), user.role, user.user_id);
+
+        Ok(Authorized { user, _permission: PhantomData })
+    }
+}
@@ -4,7 +4,7 @@ use axum::{
     middleware::Next,
     response::Response,
 };
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use std::net::SocketAddr;
 use crate::{error::{AppError, Result}, AppState};
 
@@ -26,6 +26,9 @@ impl Default for RateLimitConfig {
 }
 
 
+/// Solo cuentan para el bloqueo de una cuenta los fallos de las últimas horas.
+const LOCKOUT_FAILURE_WINDOW_HOURS: i64 = 24;
+
 #[derive(sqlx::FromRow, Debug)]
 struct RateLimit {
     blocked_until: Option<String>,
@@ -167,6 +170,16 @@ async fn update_rate_limit(
     Ok(())
 }
 
+/// Clave con la que se agrupan los intentos de una cuenta: sin espacios y en minúsculas, para que
+/// `User@x.com` y `user@x.com` compartan contador. Coincide con `lower()` de SQLite.
+pub fn normalize_attempt_email(email: &str) -> String {
+    email.trim().to_ascii_lowercase()
+}
+
+/// Registra un intento de login, ya sea de la contraseña o del código de 2FA. Un acierto limpia
+/// los fallos previos de la cuenta; un fallo que alcanza el umbral bloquea la cuenta durante un
+/// tiempo que se duplica con cada fallo extra. Sin `email` (un reto de 2FA que no existe) el
+/// intento solo queda registrado.
 pub async fn record_login_attempt(
     state: &AppState,
     ip: &str,
@@ -174,20 +187,97 @@ pub async fn record_login_attempt(
     success: bool,
     user_agent: Option<&str>,
 ) -> Result<()> { // <-- Corrected
-    sqlx::query(
+    let email = email.map(normalize_attempt_email);
+    let attempt_id = sqlx::query(
         "INSERT INTO login_attempts (ip_address, email, success, user_agent)
          VALUES (?, ?, ?, ?)"
     )
     .bind(ip)
-    .bind(email)
+    .bind(&email)
     .bind(success)
     .bind(user_agent)
     .execute(&state.db_pool)
+    .await?
+    .last_insert_rowid();
+
+    let Some(email) = email.as_deref() else {
+        return Ok(());
+    };
+
+    if success {
+        clear_failed_attempts(state, email).await?;
+        return Ok(());
+    }
+
+    let failures: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM login_attempts
+         WHERE email = ? AND success = FALSE AND cleared_at IS NULL
+         AND datetime(attempted_at) > datetime(?)"
+    )
+    .bind(email)
+    .bind((Utc::now() - Duration::hours(LOCKOUT_FAILURE_WINDOW_HOURS)).to_rfc3339())
+    .fetch_one(&state.db_pool)
     .await?;
 
+    let config = &state.config;
+    if failures >= config.login_lockout_threshold {
+        let exponent = (failures - config.login_lockout_threshold).min(16) as u32;
+        let minutes = (config.login_lockout_base_minutes * 2_i64.pow(exponent)).min(config.login_lockout_max_minutes);
+        let blocked_until = Utc::now() + Duration::minutes(minutes);
+
+        sqlx::query("UPDATE login_attempts SET blocked_until = ? WHERE id = ?")
+            .bind(blocked_until.to_rfc3339())
+            .bind(attempt_id)
+            .execute(&state.db_pool)
+            .await?;
+
+        println!("->> SECURITY | Cuenta {} bloqueada {} minutos tras {} intentos fallidos", email, minutes, failures);
+    }
+
+    Ok(())
+}
+
+/// Rechaza el login si la cuenta está bloqueada por intentos fallidos, sin importar la IP de origen.
+/// Responde 429 con `Retry-After` para que el cliente sepa cuándo reintentar.
+pub async fn check_account_lockout(state: &AppState, email: &str) -> Result<()> {
+    let email = normalize_attempt_email(email);
+    let blocked_until: Option<String> = sqlx::query_scalar(
+        "SELECT blocked_until FROM login_attempts
+         WHERE email = ? AND cleared_at IS NULL AND blocked_until IS NOT NULL
+         AND datetime(blocked_until) > datetime(?)
+         ORDER BY datetime(blocked_until) DESC LIMIT 1"
+    )
+    .bind(&email)
+    .bind(Utc::now().to_rfc3339())
+    .fetch_optional(&state.db_pool)
+    .await?;
+
+    if let Some(blocked_until) = blocked_until {
+        let remaining = DateTime::parse_from_rfc3339(&blocked_until)
+            .map(|until| (until.with_timezone(&Utc) - Utc::now()).num_seconds())
+            .unwrap_or(0);
+
+        println!("->> SECURITY | Login rechazado para la cuenta bloqueada {}", email);
+        return Err(AppError::TooManyRequests {
+            message: format!("Cuenta bloqueada temporalmente por intentos fallidos. Intenta después de {}", blocked_until),
+            retry_after_seconds: remaining.max(1) as u64,
+        });
+    }
+
     Ok(())
 }
 
+/// Marca como resueltos los intentos fallidos de una cuenta, levantando cualquier bloqueo.
+pub async fn clear_failed_attempts(state: &AppState, email: &str) -> Result<u64> {
+    let result = sqlx::query("UPDATE login_attempts SET cleared_at = ? WHERE email = ? AND cleared_at IS NULL")
+        .bind(Utc::now().to_rfc3339())
+        .bind(normalize_attempt_email(email))
+        .execute(&state.db_pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
 pub fn get_real_ip(addr: &SocketAddr, headers: &HeaderMap) -> String {
     // Prioridad para detectar la IP real
     if let Some(forwarded) = headers.get("x-forwarded-for") {
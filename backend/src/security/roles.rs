@@ -0,0 +1,222 @@
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::{
+    error::{AppError, Result},
+    models::{PermissionInfo, Role},
+    security::{
+        admin_guard::AuthenticatedUserWithRole,
+        permissions::{cached_role_permissions, Permission},
+    },
+    AppState,
+};
+
+#[derive(sqlx::FromRow, Debug)]
+struct RoleRow {
+    name: String,
+    description: Option<String>,
+    is_system: bool,
+    user_count: i64,
+    created_at: String,
+}
+
+/// Catálogo de permisos que se pueden asignar a un rol.
+pub fn list_permissions() -> Vec<PermissionInfo> {
+    Permission::ALL
+        .iter()
+        .map(|p| PermissionInfo {
+            name: p.as_str().to_string(),
+            description: p.description().to_string(),
+        })
+        .collect()
+}
+
+/// Comprueba que el rol existe antes de asignarlo a un usuario o a una invitación.
+pub async fn ensure_role_exists(db_pool: &SqlitePool, name: &str) -> Result<()> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM roles WHERE name = ?)")
+        .bind(name)
+        .fetch_one(db_pool)
+        .await?;
+
+    if !exists {
+        return Err(AppError::BadRequest(format!("Rol desconocido: {}", name)));
+    }
+    Ok(())
+}
+
+/// Comprueba que `granter` puede dar el rol a un usuario o a una invitación: sus permisos deben
+/// estar incluidos en los suyos, salvo que gestione los roles (y pueda crearse cualquiera).
+/// Así, quien solo gestiona usuarios o invitaciones no puede escalar a `admin`.
+pub async fn ensure_can_grant_role(state: &AppState, granter: &AuthenticatedUserWithRole, role: &str) -> Result<()> {
+    ensure_role_exists(&state.db_pool, role).await?;
+
+    if granter.has_permission(Permission::RolesManage) {
+        return Ok(());
+    }
+
+    let missing: Vec<&str> = cached_role_permissions(state, role)
+        .await?
+        .iter()
+        .filter(|permission| !granter.has_permission(**permission))
+        .map(Permission::as_str)
+        .collect();
+
+    if !missing.is_empty() {
+        println!("->> SECURITY | Rol '{}' denegado al usuario (ID: {}): le faltan {}", role, granter.user_id, missing.join(", "));
+        return Err(AppError::Authentication(format!(
+            "No puedes conceder el rol '{}': incluye permisos que no tienes ({})",
+            role,
+            missing.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+pub async fn list_roles(state: &AppState) -> Result<Vec<Role>> {
+    let rows: Vec<RoleRow> = sqlx::query_as(
+        "SELECT r.name, r.description, r.is_system, r.created_at,
+         (SELECT COUNT(*) FROM users u WHERE u.role = r.name) as user_count
+         FROM roles r
+         ORDER BY r.is_system DESC, r.name ASC"
+    )
+    .fetch_all(&state.db_pool)
+    .await?;
+
+    let mut roles = Vec::with_capacity(rows.len());
+    for row in rows {
+        roles.push(into_model(&state.db_pool, row).await?);
+    }
+    Ok(roles)
+}
+
+/// Crea un rol personalizado con los permisos indicados.
+pub async fn create_role(state: &AppState, name: &str, description: Option<&str>, permissions: &[String]) -> Result<Role> {
+    let permissions = parse_permissions(permissions)?;
+
+    let mut tx = state.db_pool.begin().await?;
+
+    let inserted = sqlx::query("INSERT OR IGNORE INTO roles (name, description) VALUES (?, ?)")
+        .bind(name)
+        .bind(description)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if inserted == 0 {
+        return Err(AppError::Conflict(format!("El rol '{}' ya existe", name)));
+    }
+
+    replace_permissions(&mut tx, name, &permissions).await?;
+    tx.commit().await?;
+
+    find_role(state, name).await
+}
+
+/// Sustituye la descripción y los permisos de un rol personalizado.
+pub async fn update_role(state: &AppState, name: &str, description: Option<&str>, permissions: &[String]) -> Result<Role> {
+    let role = find_role(state, name).await?;
+    if role.is_system {
+        return Err(AppError::Conflict(format!("El rol de sistema '{}' no puede modificarse", name)));
+    }
+    let permissions = parse_permissions(permissions)?;
+
+    let mut tx = state.db_pool.begin().await?;
+
+    sqlx::query("UPDATE roles SET description = ? WHERE name = ?")
+        .bind(description)
+        .bind(name)
+        .execute(&mut *tx)
+        .await?;
+
+    replace_permissions(&mut tx, name, &permissions).await?;
+    tx.commit().await?;
+    state.role_permissions.clear();
+
+    find_role(state, name).await
+}
+
+/// Elimina un rol personalizado que no esté asignado a ninguna cuenta ni invitación.
+pub async fn delete_role(state: &AppState, name: &str) -> Result<()> {
+    let role = find_role(state, name).await?;
+    if role.is_system {
+        return Err(AppError::Conflict(format!("El rol de sistema '{}' no puede eliminarse", name)));
+    }
+
+    let in_use: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE role = ?) OR EXISTS(SELECT 1 FROM invites WHERE role = ?)"
+    )
+    .bind(name)
+    .bind(name)
+    .fetch_one(&state.db_pool)
+    .await?;
+    if in_use {
+        return Err(AppError::Conflict(format!("El rol '{}' está asignado a usuarios o invitaciones", name)));
+    }
+
+    sqlx::query("DELETE FROM roles WHERE name = ?")
+        .bind(name)
+        .execute(&state.db_pool)
+        .await?;
+    state.role_permissions.clear();
+
+    Ok(())
+}
+
+async fn find_role(state: &AppState, name: &str) -> Result<Role> {
+    let row: RoleRow = sqlx::query_as(
+        "SELECT r.name, r.description, r.is_system, r.created_at,
+         (SELECT COUNT(*) FROM users u WHERE u.role = r.name) as user_count
+         FROM roles r
+         WHERE r.name = ?"
+    )
+    .bind(name)
+    .fetch_optional(&state.db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Rol '{}' no encontrado", name)))?;
+
+    into_model(&state.db_pool, row).await
+}
+
+async fn into_model(db_pool: &SqlitePool, row: RoleRow) -> Result<Role> {
+    let permissions: Vec<String> = sqlx::query_scalar(
+        "SELECT permission FROM role_permissions WHERE role = ? ORDER BY permission"
+    )
+    .bind(&row.name)
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(Role {
+        name: row.name,
+        description: row.description,
+        is_system: row.is_system,
+        permissions,
+        user_count: row.user_count,
+        created_at: row.created_at,
+    })
+}
+
+fn parse_permissions(permissions: &[String]) -> Result<Vec<Permission>> {
+    let mut parsed = Vec::new();
+    for permission in permissions {
+        let permission = Permission::from_string(permission)
+            .ok_or_else(|| AppError::BadRequest(format!("Permiso desconocido: {}", permission)))?;
+        if !parsed.contains(&permission) {
+            parsed.push(permission);
+        }
+    }
+    Ok(parsed)
+}
+
+async fn replace_permissions(conn: &mut SqliteConnection, role: &str, permissions: &[Permission]) -> Result<()> {
+    sqlx::query("DELETE FROM role_permissions WHERE role = ?")
+        .bind(role)
+        .execute(&mut *conn)
+        .await?;
+
+    for permission in permissions {
+        sqlx::query("INSERT INTO role_permissions (role, permission) VALUES (?, ?)")
+            .bind(role)
+            .bind(permission.as_str())
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use sqlx::SqliteConnection;
+
+use crate::error::{AppError, Result};
+
+/// Número máximo de personas asignadas a una tarea.
+pub const MAX_ASSIGNEES_PER_TASK: usize = 20;
+
+/// Comprueba que la tarea se pueda asignar al usuario: debe existir y tener la cuenta activa.
+pub async fn ensure_assignable(conn: &mut SqliteConnection, user_id: i32) -> Result<()> {
+    let active: Option<bool> = sqlx::query_scalar("SELECT status = 'active' FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    match active {
+        Some(true) => Ok(()),
+        Some(false) => Err(AppError::BadRequest(format!("El usuario con ID {} no está activo", user_id))),
+        None => Err(AppError::BadRequest(format!("El usuario con ID {} no existe", user_id))),
+    }
+}
+
+/// Sustituye las personas asignadas a una tarea. Solo se comprueban las que se añaden, para
+/// que una cuenta ya asignada que se haya suspendido no impida editar la tarea.
+pub async fn set_task_assignees(conn: &mut SqliteConnection, task_id: i64, user_ids: &[i32]) -> Result<()> {
+    let current: Vec<i32> = sqlx::query_scalar("SELECT user_id FROM task_assignees WHERE task_id = ?")
+        .bind(task_id)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for user_id in current.iter().filter(|id| !user_ids.contains(id)) {
+        remove_task_assignee(conn, task_id, *user_id).await?;
+    }
+    for user_id in user_ids.iter().filter(|id| !current.contains(id)) {
+        add_task_assignee(conn, task_id, *user_id).await?;
+    }
+    Ok(())
+}
+
+/// Asigna la tarea a un usuario más. Asignarla dos veces no tiene efecto.
+pub async fn add_task_assignee(conn: &mut SqliteConnection, task_id: i64, user_id: i32) -> Result<()> {
+    ensure_assignable(conn, user_id).await?;
+
+    sqlx::query("INSERT OR IGNORE INTO task_assignees (task_id, user_id) VALUES (?, ?)")
+        .bind(task_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM task_assignees WHERE task_id = ?")
+        .bind(task_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if count as usize > MAX_ASSIGNEES_PER_TASK {
+        return Err(AppError::BadRequest(format!(
+            "Una tarea no puede tener más de {} personas asignadas",
+            MAX_ASSIGNEES_PER_TASK
+        )));
+    }
+    Ok(())
+}
+
+/// Quita a un usuario de las personas asignadas a la tarea. También deja de seguirla: seguir una
+/// tarea da acceso de lectura, y quien lo quita de la tarea espera que lo pierda.
+pub async fn remove_task_assignee(conn: &mut SqliteConnection, task_id: i64, user_id: i32) -> Result<()> {
+    sqlx::query("DELETE FROM task_assignees WHERE task_id = ? AND user_id = ?")
+        .bind(task_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    set_watching(conn, task_id, user_id, false).await
+}
+
+/// Hace que otro usuario siga la tarea, lo que le da acceso de lectura. Debe tener la cuenta activa.
+pub async fn add_task_watcher(conn: &mut SqliteConnection, task_id: i64, user_id: i32) -> Result<()> {
+    ensure_assignable(conn, user_id).await?;
+    set_watching(conn, task_id, user_id, true).await
+}
+
+/// Añade o quita al usuario de los que siguen la tarea.
+pub async fn set_watching(conn: &mut SqliteConnection, task_id: i64, user_id: i32, watching: bool) -> Result<()> {
+    let query = if watching {
+        "INSERT OR IGNORE INTO task_watchers (task_id, user_id) VALUES (?, ?)"
+    } else {
+        "DELETE FROM task_watchers WHERE task_id = ? AND user_id = ?"
+    };
+
+    sqlx::query(query)
+        .bind(task_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Interpreta los filtros `assignee` y `watcher`: una lista separada por comas en la que
+/// `me` es el usuario de la petición y cualquier otro valor, el ID de un usuario.
+pub fn resolve_user_filter(value: Option<&str>, current_user_id: i32, param: &str) -> Result<Vec<i32>> {
+    let Some(value) = value else {
+        return Ok(Vec::new());
+    };
+
+    let mut user_ids = Vec::new();
+    for item in value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let user_id = if item.eq_ignore_ascii_case("me") {
+            current_user_id
+        } else {
+            item.parse().map_err(|_| {
+                AppError::BadRequest(format!("El filtro {} admite 'me' o IDs de usuario separados por comas", param))
+            })?
+        };
+        if !user_ids.contains(&user_id) {
+            user_ids.push(user_id);
+        }
+    }
+    Ok(user_ids)
+}
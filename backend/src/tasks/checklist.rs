@@ -0,0 +1,88 @@
+use chrono::Utc;
+use sqlx::SqliteConnection;
+
+use crate::{
+    error::{AppError, Result},
+    models::{ChecklistItem, UpdateChecklistItemRequest},
+};
+
+/// Número máximo de elementos en la lista de comprobación de una tarea.
+pub const MAX_CHECKLIST_ITEMS: i64 = 50;
+
+/// Añade un elemento al final de la lista de comprobación de la tarea.
+pub async fn add_checklist_item(conn: &mut SqliteConnection, task_id: i64, title: &str) -> Result<ChecklistItem> {
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM checklist_items WHERE task_id = ?")
+        .bind(task_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    if count >= MAX_CHECKLIST_ITEMS {
+        return Err(AppError::BadRequest(format!(
+            "Una tarea no puede tener más de {} elementos en su lista de comprobación",
+            MAX_CHECKLIST_ITEMS
+        )));
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let item_id = sqlx::query("INSERT INTO checklist_items (task_id, title, created_at, updated_at) VALUES (?, ?, ?, ?)")
+        .bind(task_id)
+        .bind(title.trim())
+        .bind(&now)
+        .bind(&now)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+    find_checklist_item(conn, task_id, item_id).await
+}
+
+/// Cambia el texto o el estado de un elemento de la lista de comprobación de la tarea.
+pub async fn update_checklist_item(
+    conn: &mut SqliteConnection,
+    task_id: i64,
+    item_id: i64,
+    changes: &UpdateChecklistItemRequest,
+) -> Result<ChecklistItem> {
+    let result = sqlx::query(
+        "UPDATE checklist_items SET title = COALESCE(?, title), done = COALESCE(?, done), updated_at = ?
+         WHERE id = ? AND task_id = ?"
+    )
+    .bind(changes.title.as_deref().map(str::trim))
+    .bind(changes.done)
+    .bind(Utc::now().to_rfc3339())
+    .bind(item_id)
+    .bind(task_id)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(item_not_found(item_id));
+    }
+    find_checklist_item(conn, task_id, item_id).await
+}
+
+/// Elimina un elemento de la lista de comprobación de la tarea.
+pub async fn delete_checklist_item(conn: &mut SqliteConnection, task_id: i64, item_id: i64) -> Result<()> {
+    let result = sqlx::query("DELETE FROM checklist_items WHERE id = ? AND task_id = ?")
+        .bind(item_id)
+        .bind(task_id)
+        .execute(&mut *conn)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(item_not_found(item_id));
+    }
+    Ok(())
+}
+
+async fn find_checklist_item(conn: &mut SqliteConnection, task_id: i64, item_id: i64) -> Result<ChecklistItem> {
+    sqlx::query_as("SELECT id, title, done FROM checklist_items WHERE id = ? AND task_id = ?")
+        .bind(item_id)
+        .bind(task_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| item_not_found(item_id))
+}
+
+fn item_not_found(item_id: i64) -> AppError {
+    AppError::NotFound(format!("Elemento de la lista con ID {} no encontrado", item_id))
+}
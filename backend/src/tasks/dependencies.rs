@@ -0,0 +1,69 @@
+use sqlx::SqliteConnection;
+
+use crate::error::{AppError, Result};
+
+/// Registra que `task_id` queda bloqueada por `blocker_id`. Rechaza los enlaces que cerrarían
+/// un ciclo, es decir, si `blocker_id` ya depende (directa o indirectamente) de `task_id`.
+pub async fn add_dependency(conn: &mut SqliteConnection, task_id: i64, blocker_id: i64) -> Result<()> {
+    if task_id == blocker_id {
+        return Err(AppError::BadRequest("Una tarea no puede bloquearse a sí misma".to_string()));
+    }
+
+    let creates_cycle: bool = sqlx::query_scalar(
+        "WITH RECURSIVE blockers(id) AS (
+             SELECT blocker_id FROM task_dependencies WHERE task_id = ?
+             UNION
+             SELECT d.blocker_id FROM task_dependencies d JOIN blockers b ON d.task_id = b.id
+         )
+         SELECT EXISTS (SELECT 1 FROM blockers WHERE id = ?)"
+    )
+    .bind(blocker_id)
+    .bind(task_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if creates_cycle {
+        return Err(AppError::Conflict(format!(
+            "La tarea {} ya depende de la tarea {}: el enlace formaría un ciclo",
+            blocker_id, task_id
+        )));
+    }
+
+    sqlx::query("INSERT OR IGNORE INTO task_dependencies (task_id, blocker_id) VALUES (?, ?)")
+        .bind(task_id)
+        .bind(blocker_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Elimina la dependencia entre dos tareas.
+pub async fn remove_dependency(conn: &mut SqliteConnection, task_id: i64, blocker_id: i64) -> Result<()> {
+    let result = sqlx::query("DELETE FROM task_dependencies WHERE task_id = ? AND blocker_id = ?")
+        .bind(task_id)
+        .bind(blocker_id)
+        .execute(&mut *conn)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!(
+            "La tarea {} no está bloqueada por la tarea {}",
+            task_id, blocker_id
+        )));
+    }
+    Ok(())
+}
+
+/// Tareas que bloquean a `task_id` y todavía no están terminadas.
+pub async fn open_blockers(conn: &mut SqliteConnection, task_id: i64) -> Result<Vec<i64>> {
+    let blockers = sqlx::query_scalar(
+        "SELECT d.blocker_id FROM task_dependencies d
+         JOIN tasks b ON b.id = d.blocker_id
+         WHERE d.task_id = ? AND b.status != 'done'
+         ORDER BY d.rowid"
+    )
+    .bind(task_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(blockers)
+}
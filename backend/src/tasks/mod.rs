@@ -0,0 +1,48 @@
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection};
+
+use crate::{
+    error::{AppError, Result},
+    models::Task,
+};
+
+pub mod assignment;
+pub mod checklist;
+pub mod dependencies;
+pub mod recurrence;
+pub mod subtasks;
+pub mod tags;
+
+/// Columnas que componen una `Task`. Se combinan con `TASK_FROM` y el WHERE de cada consulta.
+pub const TASK_COLUMNS: &str = "t.id, t.user_id, t.title, t.description, t.status, t.priority, t.due_date, t.created_at, t.updated_at, (SELECT tags FROM task_tag_lists WHERE task_id = t.id) as tags, COALESCE((SELECT assignees FROM task_assignee_lists WHERE task_id = t.id), '[]') as assignees, COALESCE((SELECT watchers FROM task_watcher_lists WHERE task_id = t.id), '[]') as watchers, t.parent_id, (SELECT COUNT(*) FROM tasks s WHERE s.parent_id = t.id) as subtasks_total, (SELECT COUNT(*) FROM tasks s WHERE s.parent_id = t.id AND s.status = 'done') as subtasks_done, COALESCE((SELECT items FROM task_checklist_lists WHERE task_id = t.id), '[]') as checklist, COALESCE((SELECT blockers FROM task_blocker_lists WHERE task_id = t.id), '[]') as blocked_by, EXISTS (SELECT 1 FROM task_dependencies d JOIN tasks b ON b.id = d.blocker_id WHERE d.task_id = t.id AND b.status != 'done') as blocked, t.recurrence_id, (SELECT r.rule FROM task_recurrences r WHERE r.id = t.recurrence_id AND r.active) as recurrence, u.name as owner_name, u.email as owner_email";
+
+/// Tablas de las consultas de tareas: `u` es el dueño.
+pub const TASK_FROM: &str = "FROM tasks t LEFT JOIN users u ON t.user_id = u.id";
+
+/// Condición para editar sin permisos globales: la tarea es del usuario o la tiene asignada.
+/// Recibe dos veces el ID del usuario.
+pub const TASK_ACCESS: &str = "(t.user_id = ? OR EXISTS (SELECT 1 FROM task_assignees ta WHERE ta.task_id = t.id AND ta.user_id = ?))";
+
+/// Condición para consultar sin permisos globales: la de `TASK_ACCESS` o que el usuario siga la
+/// tarea. Recibe tres veces el ID del usuario.
+pub const TASK_READ_ACCESS: &str = "(t.user_id = ? OR EXISTS (SELECT 1 FROM task_assignees ta WHERE ta.task_id = t.id AND ta.user_id = ?) OR EXISTS (SELECT 1 FROM task_watchers tw WHERE tw.task_id = t.id AND tw.user_id = ?))";
+
+/// Añade a una consulta de tareas la condición de `TASK_READ_ACCESS` para `user_id`.
+pub fn push_task_read_access(builder: &mut QueryBuilder<'_, Sqlite>, user_id: i32) {
+    builder
+        .push(" AND (t.user_id = ")
+        .push_bind(user_id)
+        .push(" OR EXISTS (SELECT 1 FROM task_assignees ta WHERE ta.task_id = t.id AND ta.user_id = ")
+        .push_bind(user_id)
+        .push(") OR EXISTS (SELECT 1 FROM task_watchers tw WHERE tw.task_id = t.id AND tw.user_id = ")
+        .push_bind(user_id)
+        .push("))");
+}
+
+/// Carga una tarea sin comprobar el acceso: quien la llama ya lo ha hecho.
+pub async fn fetch_task(conn: &mut SqliteConnection, id: i64) -> Result<Task> {
+    sqlx::query_as(&format!("SELECT {} {} WHERE t.id = ?", TASK_COLUMNS, TASK_FROM))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Tarea con ID {} no encontrada", id)))
+}
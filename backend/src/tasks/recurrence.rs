@@ -0,0 +1,416 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use sqlx::SqliteConnection;
+
+use crate::{
+    error::{AppError, Result},
+    models::{RecurrenceInput, Task},
+};
+
+/// Intervalo máximo entre repeticiones (en días, semanas o meses según la frecuencia).
+pub const MAX_RECURRENCE_INTERVAL: u32 = 365;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    fn from_string(frequency: &str) -> Option<Self> {
+        match frequency.to_ascii_lowercase().as_str() {
+            "daily" => Some(Frequency::Daily),
+            "weekly" => Some(Frequency::Weekly),
+            "monthly" => Some(Frequency::Monthly),
+            _ => None,
+        }
+    }
+
+    fn as_rrule(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        }
+    }
+}
+
+/// Cuándo termina una serie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecurrenceEnd {
+    Never,
+    /// Número total de tareas de la serie, contando la primera.
+    Count(u32),
+    /// No se crean repeticiones que venzan después de esta fecha.
+    Until(DateTime<Utc>),
+}
+
+/// Regla de repetición: el subconjunto de RRULE (RFC 5545) que admite la API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// Días de la semana de una regla semanal; vacío repite el día de la primera tarea.
+    pub weekdays: Vec<Weekday>,
+    /// Día del mes de una regla mensual; sin valor, el de la primera tarea.
+    pub month_day: Option<u32>,
+    pub end: RecurrenceEnd,
+}
+
+impl RecurrenceRule {
+    /// Construye la regla a partir de la petición: un RRULE o los campos estructurados.
+    pub fn from_input(input: &RecurrenceInput) -> Result<Self> {
+        if let Some(rrule) = &input.rrule {
+            return Self::parse_rrule(rrule);
+        }
+
+        let frequency = input
+            .frequency
+            .as_deref()
+            .and_then(Frequency::from_string)
+            .ok_or_else(|| invalid("la frecuencia debe ser daily, weekly o monthly"))?;
+        let weekdays = input
+            .weekdays
+            .iter()
+            .flatten()
+            .map(|day| parse_weekday(day))
+            .collect::<Result<Vec<_>>>()?;
+        let end = match (input.count, &input.until) {
+            (Some(_), Some(_)) => return Err(invalid("count y until no se pueden combinar")),
+            (Some(count), None) => RecurrenceEnd::Count(count),
+            (None, Some(until)) => RecurrenceEnd::Until(
+                DateTime::parse_from_rfc3339(until)
+                    .map_err(|_| invalid("until debe ser una fecha RFC 3339"))?
+                    .with_timezone(&Utc),
+            ),
+            (None, None) => RecurrenceEnd::Never,
+        };
+
+        let rule = RecurrenceRule {
+            frequency,
+            interval: input.interval.unwrap_or(1),
+            weekdays,
+            month_day: input.month_day,
+            end,
+        };
+        rule.check()?;
+        Ok(rule)
+    }
+
+    /// Interpreta un RRULE como `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=10`. Admite FREQ
+    /// (DAILY, WEEKLY, MONTHLY), INTERVAL, BYDAY, BYMONTHDAY, COUNT y UNTIL.
+    pub fn parse_rrule(rrule: &str) -> Result<Self> {
+        let rrule = rrule.trim();
+        let rrule = rrule.strip_prefix("RRULE:").unwrap_or(rrule);
+
+        let mut frequency = None;
+        let mut rule = RecurrenceRule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            weekdays: Vec::new(),
+            month_day: None,
+            end: RecurrenceEnd::Never,
+        };
+
+        for part in rrule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid(&format!("'{}' no tiene el formato CLAVE=VALOR", part)))?;
+            let number = || value.parse::<u32>().map_err(|_| invalid(&format!("{} debe ser un número", key)));
+
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(
+                        Frequency::from_string(value)
+                            .ok_or_else(|| invalid("FREQ debe ser DAILY, WEEKLY o MONTHLY"))?,
+                    )
+                }
+                "INTERVAL" => rule.interval = number()?,
+                "BYDAY" => {
+                    rule.weekdays = value.split(',').map(parse_weekday).collect::<Result<Vec<_>>>()?
+                }
+                "BYMONTHDAY" => rule.month_day = Some(number()?),
+                "COUNT" if rule.end == RecurrenceEnd::Never => rule.end = RecurrenceEnd::Count(number()?),
+                "UNTIL" if rule.end == RecurrenceEnd::Never => rule.end = RecurrenceEnd::Until(parse_until(value)?),
+                "COUNT" | "UNTIL" => return Err(invalid("COUNT y UNTIL no se pueden combinar")),
+                other => return Err(invalid(&format!("la propiedad {} no está soportada", other))),
+            }
+        }
+
+        rule.frequency = frequency.ok_or_else(|| invalid("falta FREQ"))?;
+        rule.check()?;
+        Ok(rule)
+    }
+
+    /// Forma canónica de la regla, la que se guarda y se devuelve en las respuestas.
+    pub fn to_rrule(&self) -> String {
+        let mut rrule = format!("FREQ={};INTERVAL={}", self.frequency.as_rrule(), self.interval);
+        if !self.weekdays.is_empty() {
+            let days: Vec<&str> = self.weekdays.iter().map(weekday_code).collect();
+            rrule.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+        if let Some(day) = self.month_day {
+            rrule.push_str(&format!(";BYMONTHDAY={}", day));
+        }
+        match self.end {
+            RecurrenceEnd::Never => {}
+            RecurrenceEnd::Count(count) => rrule.push_str(&format!(";COUNT={}", count)),
+            RecurrenceEnd::Until(until) => rrule.push_str(&format!(";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))),
+        }
+        rrule
+    }
+
+    /// Vencimiento de la repetición que sigue a `current`. `start` es el vencimiento de la tarea
+    /// con la que empezó la serie y fija las semanas del intervalo y el día del mes.
+    pub fn next_due_date(&self, current: DateTime<FixedOffset>, start: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self.frequency {
+            Frequency::Daily => current + Duration::days(self.interval as i64),
+            Frequency::Weekly if self.weekdays.is_empty() => current + Duration::weeks(self.interval as i64),
+            Frequency::Weekly => {
+                let week_of = |date: NaiveDate| date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                let first_week = week_of(start.date_naive());
+                (1..=7 * self.interval as i64)
+                    .map(|days| current + Duration::days(days))
+                    .find(|candidate| {
+                        let weeks = (week_of(candidate.date_naive()) - first_week).num_weeks();
+                        self.weekdays.contains(&candidate.weekday()) && weeks % self.interval as i64 == 0
+                    })
+                    .unwrap_or_else(|| current + Duration::weeks(self.interval as i64))
+            }
+            Frequency::Monthly => {
+                let day = self.month_day.unwrap_or_else(|| start.day());
+                let month = current
+                    .date_naive()
+                    .with_day(1)
+                    .and_then(|first| first.checked_add_months(Months::new(self.interval)))
+                    .unwrap_or_else(|| current.date_naive());
+                let date = (1..=day).rev().find_map(|day| month.with_day(day)).unwrap_or(month);
+                at_same_time(current, date)
+            }
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.interval == 0 || self.interval > MAX_RECURRENCE_INTERVAL {
+            return Err(invalid(&format!("el intervalo debe estar entre 1 y {}", MAX_RECURRENCE_INTERVAL)));
+        }
+        if !self.weekdays.is_empty() && self.frequency != Frequency::Weekly {
+            return Err(invalid("los días de la semana solo se admiten en reglas semanales"));
+        }
+        if let Some(day) = self.month_day {
+            if self.frequency != Frequency::Monthly || !(1..=31).contains(&day) {
+                return Err(invalid("el día del mes (1 a 31) solo se admite en reglas mensuales"));
+            }
+        }
+        if self.end == RecurrenceEnd::Count(0) {
+            return Err(invalid("COUNT debe ser al menos 1"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct Series {
+    id: i64,
+    rule: String,
+    starts_at: String,
+    occurrences: i64,
+    active: bool,
+}
+
+/// Hace que la tarea se repita con `rule`, empezando por su vencimiento. Si ya pertenece a una
+/// serie, la regla de la serie se sustituye y el recuento de COUNT empieza de nuevo desde esta tarea.
+pub async fn set_recurrence(conn: &mut SqliteConnection, task: &Task, rule: &RecurrenceRule) -> Result<()> {
+    let starts_at = task
+        .due_date
+        .as_deref()
+        .filter(|due_date| DateTime::parse_from_rfc3339(due_date).is_ok())
+        .ok_or_else(|| AppError::BadRequest("Una tarea repetitiva necesita una fecha de vencimiento válida".to_string()))?;
+    let now = Utc::now().to_rfc3339();
+
+    let series_id: Option<i64> = sqlx::query_scalar("SELECT recurrence_id FROM tasks WHERE id = ?")
+        .bind(task.id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    match series_id {
+        Some(series_id) => {
+            sqlx::query(
+                "UPDATE task_recurrences SET rule = ?, starts_at = ?, occurrences = 1, active = TRUE, updated_at = ?
+                 WHERE id = ?"
+            )
+            .bind(rule.to_rrule())
+            .bind(starts_at)
+            .bind(&now)
+            .bind(series_id)
+            .execute(&mut *conn)
+            .await?;
+        }
+        None => {
+            let series_id = sqlx::query(
+                "INSERT INTO task_recurrences (user_id, rule, starts_at, created_at, updated_at) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(task.user_id)
+            .bind(rule.to_rrule())
+            .bind(starts_at)
+            .bind(&now)
+            .bind(&now)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+
+            sqlx::query("UPDATE tasks SET recurrence_id = ? WHERE id = ?")
+                .bind(series_id)
+                .bind(task.id)
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Detiene la serie de la tarea: las tareas existentes se conservan, pero no se crean más.
+pub async fn stop_recurrence(conn: &mut SqliteConnection, task_id: i64) -> Result<()> {
+    let result = sqlx::query(
+        "UPDATE task_recurrences SET active = FALSE, updated_at = ?
+         WHERE active AND id = (SELECT recurrence_id FROM tasks WHERE id = ?)"
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(task_id)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("La tarea con ID {} no se repite", task_id)));
+    }
+    Ok(())
+}
+
+/// Crea la siguiente tarea de la serie al completar `task_id`, con el vencimiento desplazado
+/// según la regla. Solo la última tarea de una serie activa genera la siguiente; la nueva copia
+/// título, descripción, prioridad, tags, personas asignadas, seguidores y la lista de comprobación
+/// (sin marcar). Devuelve el ID de la tarea creada.
+pub async fn spawn_next_occurrence(conn: &mut SqliteConnection, task_id: i64, due_date: Option<&str>) -> Result<Option<i64>> {
+    let series: Option<Series> = sqlx::query_as(
+        "SELECT r.id, r.rule, r.starts_at, r.occurrences, r.active
+         FROM tasks t JOIN task_recurrences r ON r.id = t.recurrence_id
+         WHERE t.id = ? AND NOT EXISTS (SELECT 1 FROM tasks n WHERE n.recurrence_id = r.id AND n.id > t.id)"
+    )
+    .bind(task_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(series) = series.filter(|series| series.active) else {
+        return Ok(None);
+    };
+    let (Some(current), Ok(start)) = (
+        due_date.and_then(|due_date| DateTime::parse_from_rfc3339(due_date).ok()),
+        DateTime::parse_from_rfc3339(&series.starts_at),
+    ) else {
+        return Ok(None);
+    };
+
+    let rule = RecurrenceRule::parse_rrule(&series.rule)?;
+    let next_due = rule.next_due_date(current, start);
+    let finished = match rule.end {
+        RecurrenceEnd::Never => false,
+        RecurrenceEnd::Count(count) => series.occurrences >= count as i64,
+        RecurrenceEnd::Until(until) => next_due.with_timezone(&Utc) > until,
+    };
+
+    if finished {
+        sqlx::query("UPDATE task_recurrences SET active = FALSE, updated_at = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(series.id)
+            .execute(&mut *conn)
+            .await?;
+        return Ok(None);
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let next_id = sqlx::query(
+        "INSERT INTO tasks (user_id, title, description, status, priority, due_date, parent_id, recurrence_id, created_at, updated_at)
+         SELECT user_id, title, description, 'todo', priority, ?, parent_id, recurrence_id, ?, ?
+         FROM tasks WHERE id = ?"
+    )
+    .bind(next_due.to_rfc3339())
+    .bind(&now)
+    .bind(&now)
+    .bind(task_id)
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+
+    let copies = [
+        "INSERT INTO task_tags (task_id, tag_id, position) SELECT ?, tag_id, position FROM task_tags WHERE task_id = ?",
+        "INSERT INTO task_assignees (task_id, user_id) SELECT ?, user_id FROM task_assignees WHERE task_id = ? ORDER BY rowid",
+        "INSERT INTO task_watchers (task_id, user_id) SELECT ?, user_id FROM task_watchers WHERE task_id = ? ORDER BY rowid",
+        "INSERT INTO checklist_items (task_id, title, created_at, updated_at)
+         SELECT ?1, title, ?3, ?3 FROM checklist_items WHERE task_id = ?2 ORDER BY id",
+    ];
+    for copy in copies {
+        sqlx::query(copy)
+            .bind(next_id)
+            .bind(task_id)
+            .bind(&now)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    sqlx::query("UPDATE task_recurrences SET occurrences = occurrences + 1, updated_at = ? WHERE id = ?")
+        .bind(&now)
+        .bind(series.id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(Some(next_id))
+}
+
+fn at_same_time(current: DateTime<FixedOffset>, date: NaiveDate) -> DateTime<FixedOffset> {
+    let naive: NaiveDateTime = date.and_time(current.time());
+    current.offset().from_local_datetime(&naive).single().unwrap_or(current)
+}
+
+fn parse_weekday(day: &str) -> Result<Weekday> {
+    match day.trim().to_ascii_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(invalid(&format!("'{}' no es un día de la semana (MO, TU, WE, TH, FR, SA, SU)", day))),
+    }
+}
+
+fn weekday_code(day: &Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// UNTIL admite la forma de RRULE (`20261231` o `20261231T235959Z`) y RFC 3339.
+fn parse_until(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+        return Ok(date.and_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(23, 59, 59))
+        .map(|date| date.and_utc())
+        .ok_or_else(|| invalid("UNTIL debe ser una fecha como 20261231 o 20261231T235959Z"))
+}
+
+fn invalid(reason: &str) -> AppError {
+    AppError::BadRequest(format!("Regla de repetición inválida: {}", reason))
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::{
+    error::{AppError, Result},
+    models::Task,
+    tasks::{push_task_read_access, TASK_COLUMNS, TASK_FROM},
+};
+
+/// Niveles máximos de anidamiento, contando la tarea raíz.
+pub const MAX_TASK_DEPTH: i64 = 3;
+
+/// Comprueba que se pueda crear una subtarea bajo `parent_id` sin superar `MAX_TASK_DEPTH`.
+pub async fn ensure_subtask_depth(conn: &mut SqliteConnection, parent_id: i64) -> Result<()> {
+    let parent_depth: i64 = sqlx::query_scalar(
+        "WITH RECURSIVE ancestors(id, parent_id, depth) AS (
+             SELECT id, parent_id, 1 FROM tasks WHERE id = ?
+             UNION ALL
+             SELECT t.id, t.parent_id, a.depth + 1 FROM tasks t JOIN ancestors a ON t.id = a.parent_id
+         )
+         SELECT COALESCE(MAX(depth), 0) FROM ancestors"
+    )
+    .bind(parent_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if parent_depth >= MAX_TASK_DEPTH {
+        return Err(AppError::BadRequest(format!(
+            "Las subtareas admiten como máximo {} niveles de anidamiento",
+            MAX_TASK_DEPTH
+        )));
+    }
+    Ok(())
+}
+
+/// Rellena `subtasks` en cada tarea con todas sus subtareas, anidadas. Con `visible_to` solo se
+/// incluyen las subtareas a las que ese usuario tiene acceso.
+pub async fn attach_subtasks(db_pool: &SqlitePool, tasks: &mut [Task], visible_to: Option<i32>) -> Result<()> {
+    if tasks.is_empty() {
+        return Ok(());
+    }
+
+    let mut builder = sqlx::QueryBuilder::new("WITH RECURSIVE descendants(id) AS (SELECT id FROM tasks WHERE parent_id IN (");
+    let mut separated = builder.separated(", ");
+    for task in tasks.iter() {
+        separated.push_bind(task.id);
+    }
+    separated.push_unseparated(") UNION ALL SELECT s.id FROM tasks s JOIN descendants d ON s.parent_id = d.id) ");
+    builder.push(format_args!("SELECT {} {} WHERE t.id IN (SELECT id FROM descendants)", TASK_COLUMNS, TASK_FROM));
+    if let Some(user_id) = visible_to {
+        push_task_read_access(&mut builder, user_id);
+    }
+    builder.push(" ORDER BY t.created_at, t.id");
+
+    let descendants: Vec<Task> = builder.build_query_as().fetch_all(db_pool).await?;
+
+    let mut children: HashMap<i32, Vec<Task>> = HashMap::new();
+    for task in descendants {
+        if let Some(parent_id) = task.parent_id {
+            children.entry(parent_id).or_default().push(task);
+        }
+    }
+    for task in tasks.iter_mut() {
+        nest_subtasks(task, &mut children);
+    }
+    Ok(())
+}
+
+fn nest_subtasks(task: &mut Task, children: &mut HashMap<i32, Vec<Task>>) {
+    let mut subtasks = children.remove(&task.id).unwrap_or_default();
+    for subtask in subtasks.iter_mut() {
+        nest_subtasks(subtask, children);
+    }
+    task.subtasks = Some(subtasks);
+}
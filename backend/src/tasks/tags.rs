@@ -0,0 +1,181 @@
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::{
+    error::{AppError, Result},
+    models::{Tag, TagsInput},
+};
+
+/// Longitud máxima del nombre de un tag.
+pub const MAX_TAG_LENGTH: usize = 50;
+/// Número máximo de tags por tarea.
+pub const MAX_TAGS_PER_TASK: usize = 20;
+
+/// Normaliza un nombre de tag: sin espacios alrededor y en minúsculas, para que la
+/// búsqueda por tag sea exacta sin distinguir mayúsculas.
+pub fn normalize_tag(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Lista de tags de una petición, normalizada, sin vacíos ni repetidos y en su orden original.
+pub fn parse_tags(input: &TagsInput) -> Vec<String> {
+    let raw: Vec<&str> = match input {
+        TagsInput::List(names) => names.iter().map(String::as_str).collect(),
+        TagsInput::Csv(csv) => csv.split(',').collect(),
+    };
+
+    let mut tags: Vec<String> = Vec::new();
+    for name in raw.into_iter().map(normalize_tag) {
+        if !name.is_empty() && !tags.contains(&name) {
+            tags.push(name);
+        }
+    }
+    tags
+}
+
+/// Sustituye los tags de una tarea. Los tags pertenecen al dueño de la tarea y se crean
+/// en su catálogo si aún no existen.
+pub async fn set_task_tags(conn: &mut SqliteConnection, task_id: i64, owner_id: i32, tags: &[String]) -> Result<()> {
+    sqlx::query("DELETE FROM task_tags WHERE task_id = ?")
+        .bind(task_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for (position, name) in tags.iter().enumerate() {
+        sqlx::query("INSERT OR IGNORE INTO tags (user_id, name) VALUES (?, ?)")
+            .bind(owner_id)
+            .bind(name)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO task_tags (task_id, tag_id, position)
+             SELECT ?, id, ? FROM tags WHERE user_id = ? AND name = ?"
+        )
+        .bind(task_id)
+        .bind(position as i64)
+        .bind(owner_id)
+        .bind(name)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    prune_unused_tags(&mut *conn).await
+}
+
+/// Borra los tags que ya no usa ninguna tarea.
+pub async fn prune_unused_tags(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query("DELETE FROM tags WHERE NOT EXISTS (SELECT 1 FROM task_tags tt WHERE tt.tag_id = tags.id)")
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+/// Tags del usuario con el número de tareas que usan cada uno.
+pub async fn list_tags(db_pool: &SqlitePool, user_id: i32) -> Result<Vec<Tag>> {
+    let tags = sqlx::query_as(
+        "SELECT tg.id, tg.name, COUNT(tt.task_id) as task_count, tg.created_at
+         FROM tags tg
+         LEFT JOIN task_tags tt ON tt.tag_id = tg.id
+         WHERE tg.user_id = ?
+         GROUP BY tg.id
+         ORDER BY tg.name"
+    )
+    .bind(user_id)
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(tags)
+}
+
+/// Cambia el nombre de un tag en todas las tareas que lo usan.
+/// Si ya existe otro tag con ese nombre hay que fusionarlos con `merge_tags`.
+pub async fn rename_tag(db_pool: &SqlitePool, user_id: i32, tag_id: i64, new_name: &str) -> Result<Tag> {
+    let name = normalize_tag(new_name);
+    check_tag_name(&name)?;
+    find_tag(db_pool, user_id, tag_id).await?;
+
+    let taken: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM tags WHERE user_id = ? AND name = ? AND id != ?)")
+        .bind(user_id)
+        .bind(&name)
+        .bind(tag_id)
+        .fetch_one(db_pool)
+        .await?;
+    if taken {
+        return Err(AppError::Conflict(format!("Ya existe el tag '{}'; fusiónalos en su lugar", name)));
+    }
+
+    sqlx::query("UPDATE tags SET name = ? WHERE id = ?")
+        .bind(&name)
+        .bind(tag_id)
+        .execute(db_pool)
+        .await?;
+
+    find_tag(db_pool, user_id, tag_id).await
+}
+
+/// Fusiona `source_id` en `target_id`: las tareas con el tag de origen pasan a tener el de
+/// destino (en la misma posición) y el de origen desaparece.
+pub async fn merge_tags(db_pool: &SqlitePool, user_id: i32, source_id: i64, target_id: i64) -> Result<Tag> {
+    if source_id == target_id {
+        return Err(AppError::BadRequest("No se puede fusionar un tag consigo mismo".to_string()));
+    }
+    find_tag(db_pool, user_id, source_id).await?;
+    find_tag(db_pool, user_id, target_id).await?;
+
+    let mut tx = db_pool.begin().await?;
+
+    sqlx::query(
+        "INSERT OR IGNORE INTO task_tags (task_id, tag_id, position)
+         SELECT task_id, ?, position FROM task_tags WHERE tag_id = ?"
+    )
+    .bind(target_id)
+    .bind(source_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DELETE FROM tags WHERE id = ?")
+        .bind(source_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    find_tag(db_pool, user_id, target_id).await
+}
+
+/// Elimina un tag y lo quita de todas las tareas que lo usan.
+pub async fn delete_tag(db_pool: &SqlitePool, user_id: i32, tag_id: i64) -> Result<()> {
+    find_tag(db_pool, user_id, tag_id).await?;
+
+    sqlx::query("DELETE FROM tags WHERE id = ?")
+        .bind(tag_id)
+        .execute(db_pool)
+        .await?;
+    Ok(())
+}
+
+async fn find_tag(db_pool: &SqlitePool, user_id: i32, tag_id: i64) -> Result<Tag> {
+    sqlx::query_as(
+        "SELECT tg.id, tg.name, COUNT(tt.task_id) as task_count, tg.created_at
+         FROM tags tg
+         LEFT JOIN task_tags tt ON tt.tag_id = tg.id
+         WHERE tg.id = ? AND tg.user_id = ?
+         GROUP BY tg.id"
+    )
+    .bind(tag_id)
+    .bind(user_id)
+    .fetch_optional(db_pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Tag con ID {} no encontrado", tag_id)))
+}
+
+/// Validación compartida por las peticiones de tareas y la de renombrar tags.
+pub fn check_tag_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().count() > MAX_TAG_LENGTH || name.contains(',') {
+        return Err(AppError::BadRequest(format!(
+            "Los tags deben tener entre 1 y {} caracteres y no pueden contener comas",
+            MAX_TAG_LENGTH
+        )));
+    }
+    Ok(())
+}
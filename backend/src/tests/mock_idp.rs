@@ -0,0 +1,132 @@
+//! Minimal OpenID Connect provider used to exercise the SSO login flow end to end.
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    auth::keys::{generate_key, SigningAlgorithm},
+    models::Jwk,
+};
+
+pub const CLIENT_ID: &str = "todo-backend";
+
+struct PendingCode {
+    nonce: String,
+    code_challenge: String,
+    claims: Value,
+}
+
+struct IdpState {
+    issuer: String,
+    private_key_pem: String,
+    jwk: Jwk,
+    codes: HashMap<String, PendingCode>,
+}
+
+#[derive(Clone)]
+pub struct MockIdp {
+    pub issuer: String,
+    state: Arc<Mutex<IdpState>>,
+}
+
+impl MockIdp {
+    /// Starts the provider on a random local port.
+    pub async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let (private_key_pem, jwk) = generate_key(SigningAlgorithm::EdDsa).unwrap();
+
+        let state = Arc::new(Mutex::new(IdpState {
+            issuer: issuer.clone(),
+            private_key_pem,
+            jwk,
+            codes: HashMap::new(),
+        }));
+
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self { issuer, state }
+    }
+
+    /// Simulates the user signing in at the provider: issues an authorization code
+    /// for the request encoded in `authorization_url`, carrying `claims` in its ID token.
+    pub fn sign_in(&self, authorization_url: &str, claims: Value) -> String {
+        let url = url::Url::parse(authorization_url).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let code = crate::auth::opaque::generate_opaque_token();
+        self.state.lock().unwrap().codes.insert(
+            code.clone(),
+            PendingCode {
+                nonce: params["nonce"].clone(),
+                code_challenge: params["code_challenge"].clone(),
+                claims,
+            },
+        );
+        code
+    }
+}
+
+type SharedState = State<Arc<Mutex<IdpState>>>;
+
+async fn discovery(State(state): SharedState) -> Json<Value> {
+    let issuer = state.lock().unwrap().issuer.clone();
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", issuer),
+        "token_endpoint": format!("{}/token", issuer),
+        "jwks_uri": format!("{}/jwks", issuer),
+    }))
+}
+
+async fn jwks(State(state): SharedState) -> Json<Value> {
+    let jwk = state.lock().unwrap().jwk.clone();
+    // An encryption key the backend must skip over.
+    Json(json!({ "keys": [{ "kty": "RSA", "use": "enc", "alg": "RSA-OAEP", "kid": "enc-1", "n": "AQAB", "e": "AQAB" }, jwk] }))
+}
+
+async fn token(
+    State(state): SharedState,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    let mut state = state.lock().unwrap();
+    let pending = state.codes.remove(&form["code"]).ok_or(StatusCode::BAD_REQUEST)?;
+
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(form["code_verifier"].as_bytes()));
+    if challenge != pending.code_challenge || form["client_id"] != CLIENT_ID {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut claims = json!({
+        "iss": state.issuer,
+        "aud": CLIENT_ID,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": pending.nonce,
+    });
+    claims.as_object_mut().unwrap().extend(pending.claims.as_object().unwrap().clone());
+
+    let mut header = Header::new(jsonwebtoken::Algorithm::EdDSA);
+    header.kid = Some(state.jwk.kid.clone());
+    let key = EncodingKey::from_ed_pem(state.private_key_pem.as_bytes()).unwrap();
+    let id_token = encode(&header, &claims, &key).unwrap();
+
+    Ok(Json(json!({ "access_token": "mock-access-token", "token_type": "Bearer", "id_token": id_token })))
+}
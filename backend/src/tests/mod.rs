@@ -1 +1,2 @@
-pub mod integration;
\ No newline at end of file
+pub mod integration;
+pub mod mock_idp;
@@ -287,7 +287,7 @@ const Dashboard = () => {
         priority: task.priority,
         due_date: task.due_date ? new Date(task.due_date) : null,
         tags: task.tags || '',
-        assigned_to: task.assigned_to || 'unassigned'
+        assigned_to: task.assignees?.length ? String(task.assignees[0].id) : 'unassigned'
       });
     } else {
       setEditingTask(null);
@@ -318,7 +318,8 @@ const Dashboard = () => {
       const taskData = {
         ...taskForm,
         due_date: taskForm.due_date ? format(taskForm.due_date, "yyyy-MM-dd'T'HH:mm:ss.SSS'Z'") : null,
-        assigned_to: taskForm.assigned_to === 'unassigned' ? '' : taskForm.assigned_to
+        assigned_to: undefined,
+        assignees: taskForm.assigned_to === 'unassigned' ? [] : [Number(taskForm.assigned_to)]
       };
 
       if (editingTask) {
@@ -651,10 +652,10 @@ const Dashboard = () => {
                                 </span>
                               </div>
                             )}
-                            {task.assigned_to && (
+                            {task.assignees?.length > 0 && (
                               <div className="flex items-center space-x-1">
                                 <User className="w-4 h-4" />
-                                <span>{task.assigned_to}</span>
+                                <span>{task.assignees.map(member => member.name).join(', ')}</span>
                               </div>
                             )}
                             {isAdmin && task.owner_name && (
@@ -830,7 +831,7 @@ const Dashboard = () => {
                   <SelectContent>
                     <SelectItem value="unassigned">{t('dashboard.unassigned')}</SelectItem>
                     {users.map(user => (
-                      <SelectItem key={user.id} value={user.name}>
+                      <SelectItem key={user.id} value={String(user.id)}>
                         {user.name}
                       </SelectItem>
                     ))}
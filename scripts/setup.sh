@@ -15,19 +15,9 @@ if [ ! -f .env ]; then
     echo "✅ Archivo .env creado. Por favor, revisa y ajusta las configuraciones."
 fi
 
-# Instalar sqlx-cli si no está instalado
-if ! command -v sqlx &> /dev/null; then
-    echo "📦 Instalando sqlx-cli..."
-    cargo install sqlx-cli --no-default-features --features sqlite
-fi
-
 # Navegar al directorio del backend
 cd backend
 
-# Ejecutar migraciones
-echo "🗄️ Ejecutando migraciones de base de datos..."
-sqlx migrate run
-
 # Compilar el proyecto
 echo "🔨 Compilando proyecto..."
 cargo build
@@ -39,6 +29,7 @@ echo "✅ ¡Configuración completada!"
 echo ""
 echo "Para iniciar el servidor ejecuta:"
 echo "  cargo run"
+echo "(las migraciones de base de datos se aplican al arrancar)"
 echo ""
 echo "Una vez iniciado, visita:"
 echo "  - API: http://127.0.0.1:3000"